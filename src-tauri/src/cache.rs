@@ -0,0 +1,216 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Default size budget for cached bodies.
+pub const DEFAULT_BUDGET: usize = 64 * 1024 * 1024;
+
+/// In-memory LRU cache of patched asset bodies served by `model://`.
+///
+/// Entries are keyed by path and stamped with the source file's mtime and
+/// length; a lookup with a different stamp drops the stale entry.
+pub struct AssetCache(Mutex<Inner>);
+
+struct Inner {
+    entries: HashMap<PathBuf, Entry>,
+    budget: usize,
+    bytes: usize,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+struct Entry {
+    stamp: Stamp,
+    data: Vec<u8>,
+    last_used: u64,
+}
+
+/// Identity of a file version on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    pub modified: Option<SystemTime>,
+    pub len: u64,
+}
+
+impl Stamp {
+    pub fn of(meta: &std::fs::Metadata) -> Self {
+        Self {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub bytes: usize,
+    pub budget: usize,
+}
+
+impl Default for AssetCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUDGET)
+    }
+}
+
+impl AssetCache {
+    pub fn new(budget: usize) -> Self {
+        Self(Mutex::new(Inner {
+            entries: HashMap::new(),
+            budget,
+            bytes: 0,
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }))
+    }
+
+    /// Return the cached body for `path` if it was stored for the same `stamp`.
+    pub fn get(&self, path: &Path, stamp: Stamp) -> Option<Vec<u8>> {
+        let mut inner = self.0.lock().unwrap();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        match inner.entries.get_mut(path) {
+            Some(entry) if entry.stamp == stamp => {
+                entry.last_used = tick;
+                let data = entry.data.clone();
+                inner.hits += 1;
+                Some(data)
+            }
+            Some(_) => {
+                // File changed on disk since it was cached
+                inner.remove(path);
+                inner.misses += 1;
+                None
+            }
+            None => {
+                inner.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Store `data` for `path`, evicting least recently used entries to stay
+    /// within the budget. Bodies larger than the whole budget are not cached.
+    pub fn insert(&self, path: &Path, stamp: Stamp, data: Vec<u8>) {
+        let mut inner = self.0.lock().unwrap();
+        inner.remove(path);
+        if data.len() > inner.budget {
+            return;
+        }
+        while inner.bytes + data.len() > inner.budget {
+            let Some(oldest) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(p, _)| p.clone())
+            else {
+                break;
+            };
+            inner.remove(&oldest);
+        }
+        inner.tick += 1;
+        inner.bytes += data.len();
+        let last_used = inner.tick;
+        inner.entries.insert(
+            path.to_path_buf(),
+            Entry {
+                stamp,
+                data,
+                last_used,
+            },
+        );
+    }
+
+    /// Drop every entry, e.g. after the set of enabled fixups changed.
+    pub fn clear(&self) {
+        let mut inner = self.0.lock().unwrap();
+        inner.entries.clear();
+        inner.bytes = 0;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.0.lock().unwrap();
+        CacheStats {
+            hits: inner.hits,
+            misses: inner.misses,
+            entries: inner.entries.len(),
+            bytes: inner.bytes,
+            budget: inner.budget,
+        }
+    }
+}
+
+impl Inner {
+    fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.entries.remove(path) {
+            self.bytes -= entry.data.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn stamp(secs: u64, len: u64) -> Stamp {
+        Stamp {
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            len,
+        }
+    }
+
+    #[test]
+    fn test_hit_and_miss() {
+        let cache = AssetCache::new(1024);
+        let path = Path::new("/m/a.json");
+        assert!(cache.get(path, stamp(1, 3)).is_none());
+        cache.insert(path, stamp(1, 3), b"abc".to_vec());
+        assert_eq!(cache.get(path, stamp(1, 3)).unwrap(), b"abc");
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!((stats.entries, stats.bytes), (1, 3));
+    }
+
+    #[test]
+    fn test_changed_file_invalidates() {
+        let cache = AssetCache::new(1024);
+        let path = Path::new("/m/a.json");
+        cache.insert(path, stamp(1, 3), b"abc".to_vec());
+        assert!(cache.get(path, stamp(2, 3)).is_none());
+        assert!(cache.get(path, stamp(1, 3)).is_none());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = AssetCache::new(10);
+        let (a, b, c) = (Path::new("/a"), Path::new("/b"), Path::new("/c"));
+        cache.insert(a, stamp(1, 4), vec![0; 4]);
+        cache.insert(b, stamp(1, 4), vec![0; 4]);
+        // Touch `a` so `b` becomes least recently used
+        assert!(cache.get(a, stamp(1, 4)).is_some());
+        cache.insert(c, stamp(1, 4), vec![0; 4]);
+
+        assert!(cache.get(a, stamp(1, 4)).is_some());
+        assert!(cache.get(b, stamp(1, 4)).is_none());
+        assert!(cache.get(c, stamp(1, 4)).is_some());
+        assert_eq!(cache.stats().bytes, 8);
+    }
+
+    #[test]
+    fn test_oversized_not_cached() {
+        let cache = AssetCache::new(4);
+        cache.insert(Path::new("/a"), stamp(1, 8), vec![0; 8]);
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
@@ -1,239 +1,626 @@
-use rusqlite::Connection;
+use crate::migrations;
+use crate::settings::{self, Settings};
+use rusqlite::{Connection, OptionalExtension};
 use serde::Serialize;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tauri::Manager;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct AppConfig {
     pub current_model: Option<String>,
     pub models: Vec<String>,
-    pub show_border: bool,
-    pub model_x: Option<f64>,
-    pub model_y: Option<f64>,
-    pub model_scale: Option<f64>,
-    pub tap_motion: bool,
-    pub show_hit_areas: bool,
-    pub lock_model: bool,
-    pub mouse_tracking: bool,
-}
-
-fn db_path(app: &tauri::AppHandle) -> PathBuf {
-    let dir = app
-        .path()
-        .app_data_dir()
-        .expect("Failed to get app data dir");
-    std::fs::create_dir_all(&dir).ok();
-    dir.join("rive2d.db")
-}
-
-fn open_db(app: &tauri::AppHandle) -> Connection {
-    let path = db_path(app);
-    let conn = Connection::open(path).expect("Failed to open database");
-    conn.execute_batch(
-        "CREATE TABLE IF NOT EXISTS config (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL
-        );
-        CREATE TABLE IF NOT EXISTS models (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            path TEXT NOT NULL UNIQUE,
-            added_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        );",
-    )
-    .expect("Failed to initialize database");
-
-    // Add source_hash column if missing (migration for existing DBs)
-    let has_hash: bool = conn
-        .prepare("SELECT source_hash FROM models LIMIT 0")
-        .is_ok();
-    if !has_hash {
-        conn.execute_batch("ALTER TABLE models ADD COLUMN source_hash TEXT")
-            .ok();
+    /// Effective settings of the current model
+    #[serde(flatten)]
+    pub settings: Settings,
+    /// Global settings, the fallback for models without overrides
+    pub global: Settings,
+}
+
+/// Per-model data set by the user, kept in `model_meta`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ModelMeta {
+    /// Display name
+    pub name: Option<String>,
+    /// Motion overrides, as JSON
+    pub motions: Option<String>,
+    /// Preview image path
+    pub preview: Option<String>,
+    pub intimacy: Option<f64>,
+    /// Descriptor of the selected variant
+    pub active_variant: Option<String>,
+}
+
+/// A selectable costume of a library entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelVariant {
+    pub path: String,
+    pub name: Option<String>,
+    pub preview: Option<String>,
+}
+
+fn db_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join("rive2d.db"))
+}
+
+/// The app's database connection, shared through Tauri state.
+pub struct Db(Mutex<Connection>);
+
+impl Db {
+    /// Open the database at `path` in WAL mode and migrate it.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut conn = Connection::open(path).map_err(|e| e.to_string())?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))
+            .map_err(|e| e.to_string())?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "synchronous", "NORMAL")
+            .map_err(|e| e.to_string())?;
+        // model_meta rows go with their model
+        conn.pragma_update(None, "foreign_keys", true)
+            .map_err(|e| e.to_string())?;
+        conn.set_prepared_statement_cache_capacity(64);
+        migrations::migrate(&mut conn, &path.with_extension("db.bak"))?;
+        Ok(Self(Mutex::new(conn)))
     }
+}
 
-    conn
+/// Open the database and put it in Tauri state. Must run before any other
+/// function here.
+pub fn init(app: &tauri::AppHandle) -> Result<(), String> {
+    let db = Db::open(&db_path(app)?)?;
+    app.manage(db);
+    Ok(())
 }
 
-pub fn load(app: &tauri::AppHandle) -> AppConfig {
-    let conn = open_db(app);
+/// Run `f` on the shared connection.
+fn with_db<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    let db = app.try_state::<Db>().ok_or("Database not initialized")?;
+    // A panic mid-query leaves nothing half-done outside a transaction,
+    // and transactions roll back on drop
+    let mut conn = db.0.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut conn).map_err(|e| e.to_string())
+}
 
-    let current_model: Option<String> = conn
-        .query_row(
-            "SELECT value FROM config WHERE key = 'current_model'",
-            [],
-            |row| row.get(0),
-        )
-        .ok();
+/// Rows of a single-column query.
+fn column<T: rusqlite::types::FromSql>(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> rusqlite::Result<Vec<T>> {
+    let mut stmt = conn.prepare_cached(sql)?;
+    let rows = stmt.query_map(params, |row| row.get(0))?;
+    rows.collect()
+}
 
-    let mut stmt = conn
-        .prepare("SELECT path FROM models ORDER BY added_at DESC")
-        .unwrap();
-    let models: Vec<String> = stmt
-        .query_map([], |row| row.get(0))
-        .unwrap()
-        .filter_map(|r| r.ok())
-        .collect();
-
-    let show_border: bool = conn
-        .query_row(
-            "SELECT value FROM config WHERE key = 'show_border'",
-            [],
-            |row| row.get::<_, String>(0),
-        )
-        .map(|v| v == "true")
-        .unwrap_or(false);
-
-    let model_x: Option<f64> = conn
-        .query_row(
-            "SELECT value FROM config WHERE key = 'model_x'",
-            [],
-            |row| row.get::<_, String>(0),
-        )
-        .ok()
-        .and_then(|v| v.parse().ok());
-
-    let model_y: Option<f64> = conn
-        .query_row(
-            "SELECT value FROM config WHERE key = 'model_y'",
-            [],
-            |row| row.get::<_, String>(0),
-        )
-        .ok()
-        .and_then(|v| v.parse().ok());
-
-    let model_scale: Option<f64> = conn
-        .query_row(
-            "SELECT value FROM config WHERE key = 'model_scale'",
-            [],
-            |row| row.get::<_, String>(0),
-        )
-        .ok()
-        .and_then(|v| v.parse().ok());
-
-    let tap_motion: bool = conn
-        .query_row(
-            "SELECT value FROM config WHERE key = 'tap_motion'",
-            [],
-            |row| row.get::<_, String>(0),
-        )
-        .map(|v| v == "true")
-        .unwrap_or(true);
-
-    let show_hit_areas: bool = conn
-        .query_row(
-            "SELECT value FROM config WHERE key = 'show_hit_areas'",
-            [],
-            |row| row.get::<_, String>(0),
-        )
-        .map(|v| v == "true")
-        .unwrap_or(false);
-
-    let lock_model: bool = conn
-        .query_row(
-            "SELECT value FROM config WHERE key = 'lock_model'",
-            [],
-            |row| row.get::<_, String>(0),
-        )
-        .map(|v| v == "true")
-        .unwrap_or(false);
-
-    let mouse_tracking: bool = conn
-        .query_row(
-            "SELECT value FROM config WHERE key = 'mouse_tracking'",
-            [],
-            |row| row.get::<_, String>(0),
-        )
-        .map(|v| v == "true")
-        .unwrap_or(true);
-
-    AppConfig {
-        current_model,
-        models,
-        show_border,
-        model_x,
-        model_y,
-        model_scale,
-        tap_motion,
-        show_hit_areas,
-        lock_model,
-        mouse_tracking,
+pub fn load(app: &tauri::AppHandle) -> Result<AppConfig, String> {
+    with_db(app, |conn| {
+        let current_model = current_model(conn)?;
+        let values = setting_values(conn)?;
+        let overrides = match &current_model {
+            Some(path) => model_overrides(conn, path)?,
+            None => HashMap::new(),
+        };
+        Ok(AppConfig {
+            models: column(conn, "SELECT path FROM models ORDER BY added_at DESC", [])?,
+            settings: Settings::with_overrides(&values, &overrides),
+            global: Settings::from_values(&values),
+            current_model,
+        })
+    })
+}
+
+fn current_model(conn: &Connection) -> rusqlite::Result<Option<String>> {
+    conn.prepare_cached("SELECT value FROM config WHERE key = 'current_model'")?
+        .query_row([], |row| row.get(0))
+        .optional()
+}
+
+/// The global settings, see `settings::Settings`.
+pub fn settings(app: &tauri::AppHandle) -> Result<Settings, String> {
+    with_db(app, |conn| {
+        Ok(Settings::from_values(&setting_values(conn)?))
+    })
+}
+
+/// Effective value of the setting `key` for the current model: its
+/// override if it has one, else the global value, else the default.
+pub fn effective_setting(app: &tauri::AppHandle, key: &str) -> Result<Option<String>, String> {
+    let stored = with_db(app, |conn| stored_setting(conn, key))?;
+    let values = stored
+        .map(|value| HashMap::from([(key.to_string(), value)]))
+        .unwrap_or_default();
+    Ok(Settings::from_values(&values).get(key))
+}
+
+/// Stored value of `key` for the current model, without loading the rest
+/// of the config.
+fn stored_setting(conn: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    if settings::PER_MODEL.contains(&key) {
+        let value = conn
+            .prepare_cached(
+                "SELECT s.value FROM model_settings s
+                 JOIN models m ON m.id = s.model_id
+                 JOIN config c ON c.key = 'current_model' AND c.value = m.path
+                 WHERE s.key = ?1",
+            )?
+            .query_row([key], |row| row.get(0))
+            .optional()?;
+        if value.is_some() {
+            return Ok(value);
+        }
     }
+    conn.prepare_cached("SELECT value FROM config WHERE key = ?1")?
+        .query_row([key], |row| row.get(0))
+        .optional()
 }
 
-pub fn has_hash(app: &tauri::AppHandle, hash: &str) -> bool {
-    let conn = open_db(app);
-    conn.query_row(
-        "SELECT 1 FROM models WHERE source_hash = ?1",
-        [hash],
-        |_| Ok(()),
-    )
-    .is_ok()
-}
-
-pub fn add_model(app: &tauri::AppHandle, path: &str, source_hash: Option<&str>) {
-    let conn = open_db(app);
-    conn.execute(
-        "INSERT OR IGNORE INTO models (path, source_hash) VALUES (?1, ?2)",
-        rusqlite::params![path, source_hash],
-    )
-    .ok();
-}
-
-pub fn remove_model(app: &tauri::AppHandle, path: &str) {
-    let conn = open_db(app);
-    conn.execute("DELETE FROM models WHERE path = ?1", [path])
-        .ok();
-
-    // Clear current_model if it was the removed one
-    let current: Option<String> = conn
-        .query_row(
-            "SELECT value FROM config WHERE key = 'current_model'",
-            [],
-            |row| row.get(0),
-        )
-        .ok();
-    if current.as_deref() == Some(path) {
-        conn.execute("DELETE FROM config WHERE key = 'current_model'", [])
-            .ok();
+/// Stored values of the global settings.
+fn setting_values(conn: &Connection) -> rusqlite::Result<HashMap<String, String>> {
+    let placeholders = vec!["?"; settings::KEYS.len()].join(", ");
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT key, value FROM config WHERE key IN ({})",
+        placeholders
+    ))?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(settings::KEYS), |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    rows.collect()
+}
+
+/// Settings overridden by the model at `path`, see `settings::PER_MODEL`.
+fn model_overrides(conn: &Connection, path: &str) -> rusqlite::Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT s.key, s.value FROM model_settings s
+         JOIN models m ON m.id = s.model_id WHERE m.path = ?1",
+    )?;
+    let rows = stmt.query_map([path], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Override the setting `key` for the registered model at `path`, or go
+/// back to the global value if `value` is `None`.
+pub fn set_model_setting(
+    app: &tauri::AppHandle,
+    path: &str,
+    key: &str,
+    value: Option<&str>,
+) -> Result<(), String> {
+    let registered = with_db(app, |conn| {
+        let Some(id) = model_id(conn, path)? else {
+            return Ok(false);
+        };
+        match value {
+            Some(value) => conn
+                .prepare_cached(
+                    "INSERT OR REPLACE INTO model_settings (model_id, key, value)
+                     VALUES (?1, ?2, ?3)",
+                )?
+                .execute(rusqlite::params![id, key, value])?,
+            None => conn
+                .prepare_cached("DELETE FROM model_settings WHERE model_id = ?1 AND key = ?2")?
+                .execute(rusqlite::params![id, key])?,
+        };
+        Ok(true)
+    })?;
+    if registered {
+        Ok(())
+    } else {
+        Err(format!("Model not registered: {}", path))
     }
 }
 
-pub fn get_setting(app: &tauri::AppHandle, key: &str) -> Option<String> {
-    let conn = open_db(app);
-    conn.query_row(
-        "SELECT value FROM config WHERE key = ?1",
-        [key],
-        |row| row.get(0),
-    )
-    .ok()
+fn model_id(conn: &Connection, path: &str) -> rusqlite::Result<Option<i64>> {
+    conn.prepare_cached("SELECT id FROM models WHERE path = ?1")?
+        .query_row([path], |row| row.get(0))
+        .optional()
+}
+
+/// Paths of all registered model descriptors.
+pub fn model_paths(app: &tauri::AppHandle) -> Result<Vec<String>, String> {
+    with_db(app, |conn| column(conn, "SELECT path FROM models", []))
+}
+
+pub fn has_hash(app: &tauri::AppHandle, hash: &str) -> Result<bool, String> {
+    with_db(app, |conn| {
+        conn.prepare_cached("SELECT EXISTS (SELECT 1 FROM models WHERE source_hash = ?1)")?
+            .query_row([hash], |row| row.get(0))
+    })
+}
+
+/// Path of a registered model imported from a file with the given hash.
+pub fn find_by_source_hash(
+    app: &tauri::AppHandle,
+    source_hash: &str,
+) -> Result<Option<String>, String> {
+    with_db(app, |conn| {
+        conn.prepare_cached(
+            "SELECT path FROM models WHERE source_hash = ?1 ORDER BY added_at LIMIT 1",
+        )?
+        .query_row([source_hash], |row| row.get(0))
+        .optional()
+    })
+}
+
+/// Whether `path` is a registered model or variant descriptor.
+pub fn is_registered(app: &tauri::AppHandle, path: &str) -> Result<bool, String> {
+    with_db(app, |conn| {
+        conn.prepare_cached(
+            "SELECT EXISTS (SELECT 1 FROM models WHERE path = ?1)
+                OR EXISTS (SELECT 1 FROM model_variants WHERE path = ?1)",
+        )?
+        .query_row([path], |row| row.get(0))
+    })
+}
+
+/// Whether a registered model lives inside `dir`.
+pub fn has_model_in(app: &tauri::AppHandle, dir: &Path) -> Result<bool, String> {
+    let prefix = format!("{}{}", dir.to_string_lossy(), std::path::MAIN_SEPARATOR);
+    with_db(app, |conn| {
+        conn.prepare_cached(
+            "SELECT EXISTS (SELECT 1 FROM models WHERE substr(path, 1, length(?1)) = ?1)",
+        )?
+        .query_row([prefix], |row| row.get(0))
+    })
+}
+
+pub fn add_model(
+    app: &tauri::AppHandle,
+    path: &str,
+    source_hash: Option<&str>,
+    asset_hash: Option<&str>,
+) -> Result<(), String> {
+    add_model_with_variants(app, path, source_hash, asset_hash, &[])
+}
+
+/// Path of a registered model with the given content fingerprint.
+pub fn find_by_asset_hash(
+    app: &tauri::AppHandle,
+    asset_hash: &str,
+) -> Result<Option<String>, String> {
+    with_db(app, |conn| {
+        conn.prepare_cached(
+            "SELECT path FROM models WHERE asset_hash = ?1 ORDER BY added_at LIMIT 1",
+        )?
+        .query_row([asset_hash], |row| row.get(0))
+        .optional()
+    })
+}
+
+/// Registered models whose content fingerprint was never computed.
+pub fn models_without_asset_hash(app: &tauri::AppHandle) -> Result<Vec<String>, String> {
+    with_db(app, |conn| {
+        column(conn, "SELECT path FROM models WHERE asset_hash IS NULL", [])
+    })
+}
+
+pub fn set_asset_hash(app: &tauri::AppHandle, path: &str, asset_hash: &str) -> Result<(), String> {
+    with_db(app, |conn| {
+        conn.prepare_cached("UPDATE models SET asset_hash = ?2 WHERE path = ?1")?
+            .execute([path, asset_hash])
+            .map(|_| ())
+    })
+}
+
+/// Register a model together with its selectable costumes. `path` is the
+/// library entry and default variant; `variants` lists all of them in order.
+pub fn add_model_with_variants(
+    app: &tauri::AppHandle,
+    path: &str,
+    source_hash: Option<&str>,
+    asset_hash: Option<&str>,
+    variants: &[ModelVariant],
+) -> Result<(), String> {
+    with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        tx.prepare_cached(
+            "INSERT OR IGNORE INTO models (path, source_hash, asset_hash) VALUES (?1, ?2, ?3)",
+        )?
+        .execute(rusqlite::params![path, source_hash, asset_hash])?;
+        for (i, variant) in variants.iter().enumerate() {
+            tx.prepare_cached(
+                "INSERT OR REPLACE INTO model_variants (model_id, path, name, preview, position)
+                 SELECT id, ?2, ?3, ?4, ?5 FROM models WHERE path = ?1",
+            )?
+            .execute(rusqlite::params![
+                path,
+                variant.path,
+                variant.name,
+                variant.preview,
+                i as i64
+            ])?;
+        }
+        tx.commit()
+    })
+}
+
+/// Costumes registered for the library entry at `model_path`, default first.
+/// Empty for models without variants.
+pub fn model_variants(
+    app: &tauri::AppHandle,
+    model_path: &str,
+) -> Result<Vec<ModelVariant>, String> {
+    with_db(app, |conn| {
+        let mut stmt = conn.prepare_cached(
+            "SELECT v.path, v.name, v.preview FROM model_variants v
+             JOIN models m ON m.id = v.model_id WHERE m.path = ?1 ORDER BY v.position",
+        )?;
+        let rows = stmt.query_map([model_path], |row| {
+            Ok(ModelVariant {
+                path: row.get(0)?,
+                name: row.get(1)?,
+                preview: row.get(2)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// Append `path` to the variants of the registered model at `model_path`,
+/// registering the entry itself as the default variant first if it had none.
+pub fn add_model_variant(
+    app: &tauri::AppHandle,
+    model_path: &str,
+    path: &str,
+) -> Result<(), String> {
+    with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        tx.prepare_cached(
+            "INSERT OR IGNORE INTO model_variants (model_id, path, position)
+             SELECT id, path, 0 FROM models WHERE path = ?1",
+        )?
+        .execute([model_path])?;
+        tx.prepare_cached(
+            "INSERT OR IGNORE INTO model_variants (model_id, path, position)
+             SELECT m.id, ?2, COALESCE(MAX(v.position), 0) + 1
+             FROM models m LEFT JOIN model_variants v ON v.model_id = m.id
+             WHERE m.path = ?1 GROUP BY m.id",
+        )?
+        .execute([model_path, path])?;
+        tx.commit()
+    })
+}
+
+/// Descriptor to load for the library entry at `model_path`: the selected
+/// variant if it is still registered, otherwise the entry itself.
+pub fn active_variant_path(app: &tauri::AppHandle, model_path: &str) -> Result<String, String> {
+    let active: Option<String> = with_db(app, |conn| {
+        conn.prepare_cached(
+            "SELECT v.path FROM models m
+             JOIN model_meta mm ON mm.model_id = m.id
+             JOIN model_variants v ON v.model_id = m.id AND v.path = mm.active_variant
+             WHERE m.path = ?1",
+        )?
+        .query_row([model_path], |row| row.get(0))
+        .optional()
+    })?;
+    Ok(active.unwrap_or_else(|| model_path.to_string()))
+}
+
+/// Unregister the model at `path`; its metadata, settings and variants go
+/// with it.
+pub fn remove_model(app: &tauri::AppHandle, path: &str) -> Result<(), String> {
+    with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        tx.prepare_cached("DELETE FROM models WHERE path = ?1")?
+            .execute([path])?;
+
+        // Clear current_model if it was the removed one
+        if current_model(&tx)?.as_deref() == Some(path) {
+            tx.prepare_cached("DELETE FROM config WHERE key = 'current_model'")?
+                .execute([])?;
+        }
+        tx.commit()
+    })
 }
 
-pub fn set_setting(app: &tauri::AppHandle, key: &str, value: &str) {
-    let conn = open_db(app);
-    conn.execute(
-        "INSERT OR REPLACE INTO config (key, value) VALUES (?1, ?2)",
-        [key, value],
-    )
-    .ok();
+/// Put the registered model `new` in place of `old` in one transaction:
+/// `old` is unregistered along with the config keys starting with
+/// `stale_prefix`, and `new` becomes the current model if `old` was.
+/// Returns whether it was.
+pub fn replace_model(
+    app: &tauri::AppHandle,
+    old: &str,
+    new: &str,
+    stale_prefix: Option<&str>,
+) -> Result<bool, String> {
+    with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        let was_current = current_model(&tx)?.as_deref() == Some(old);
+        tx.prepare_cached("DELETE FROM models WHERE path = ?1")?
+            .execute([old])?;
+        if let Some(prefix) = stale_prefix {
+            tx.prepare_cached("DELETE FROM config WHERE substr(key, 1, length(?1)) = ?1")?
+                .execute([prefix])?;
+        }
+        if was_current {
+            tx.prepare_cached(
+                "INSERT OR REPLACE INTO config (key, value) VALUES ('current_model', ?1)",
+            )?
+            .execute([new])?;
+        }
+        tx.commit()?;
+        Ok(was_current)
+    })
 }
 
-pub fn delete_settings(app: &tauri::AppHandle, keys: &[&str]) {
-    let conn = open_db(app);
-    for key in keys {
-        conn.execute("DELETE FROM config WHERE key = ?1", [key]).ok();
+/// Metadata of the library entry at `path`; empty if none was set.
+pub fn model_meta(app: &tauri::AppHandle, path: &str) -> Result<ModelMeta, String> {
+    with_db(app, |conn| {
+        Ok(read_model_meta(conn, path)?
+            .map(|(_, meta)| meta)
+            .unwrap_or_default())
+    })
+}
+
+/// Change the metadata of the registered model at `path` with `f`.
+pub fn update_model_meta(
+    app: &tauri::AppHandle,
+    path: &str,
+    f: impl FnOnce(&mut ModelMeta),
+) -> Result<(), String> {
+    let result = with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        let Some((id, mut meta)) = read_model_meta(&tx, path)? else {
+            return Ok(false);
+        };
+        f(&mut meta);
+        tx.prepare_cached(
+            "INSERT OR REPLACE INTO model_meta
+                (model_id, name, motions, preview, intimacy, active_variant)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?
+        .execute(rusqlite::params![
+            id,
+            meta.name,
+            meta.motions,
+            meta.preview,
+            meta.intimacy,
+            meta.active_variant
+        ])?;
+        tx.commit()?;
+        Ok(true)
+    })?;
+    if result {
+        Ok(())
+    } else {
+        Err(format!("Model not registered: {}", path))
     }
 }
 
-pub fn set_model(app: &tauri::AppHandle, path: &str) {
-    let conn = open_db(app);
-    conn.execute(
-        "INSERT OR REPLACE INTO config (key, value) VALUES ('current_model', ?1)",
-        [path],
-    )
-    .ok();
-    conn.execute(
-        "INSERT OR IGNORE INTO models (path) VALUES (?1)",
-        [path],
-    )
-    .ok();
+/// Id and metadata of the registered model at `path`.
+fn read_model_meta(conn: &Connection, path: &str) -> rusqlite::Result<Option<(i64, ModelMeta)>> {
+    conn.prepare_cached(
+        "SELECT m.id, mm.name, mm.motions, mm.preview, mm.intimacy, mm.active_variant
+         FROM models m LEFT JOIN model_meta mm ON mm.model_id = m.id
+         WHERE m.path = ?1",
+    )?
+    .query_row([path], |row| {
+        Ok((
+            row.get(0)?,
+            ModelMeta {
+                name: row.get(1)?,
+                motions: row.get(2)?,
+                preview: row.get(3)?,
+                intimacy: row.get(4)?,
+                active_variant: row.get(5)?,
+            },
+        ))
+    })
+    .optional()
+}
+
+pub fn get_setting(app: &tauri::AppHandle, key: &str) -> Result<Option<String>, String> {
+    with_db(app, |conn| {
+        conn.prepare_cached("SELECT value FROM config WHERE key = ?1")?
+            .query_row([key], |row| row.get(0))
+            .optional()
+    })
+}
+
+/// All settings whose key starts with `prefix`, keyed by full key.
+pub fn get_settings_with_prefix(
+    app: &tauri::AppHandle,
+    prefix: &str,
+) -> Result<HashMap<String, String>, String> {
+    with_db(app, |conn| {
+        let mut stmt = conn.prepare_cached(
+            "SELECT key, value FROM config WHERE substr(key, 1, length(?1)) = ?1",
+        )?;
+        let rows = stmt.query_map([prefix], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    })
+}
+
+pub fn set_setting(app: &tauri::AppHandle, key: &str, value: &str) -> Result<(), String> {
+    with_db(app, |conn| {
+        conn.prepare_cached("INSERT OR REPLACE INTO config (key, value) VALUES (?1, ?2)")?
+            .execute([key, value])
+            .map(|_| ())
+    })
+}
+
+pub fn delete_settings(app: &tauri::AppHandle, keys: &[&str]) -> Result<(), String> {
+    with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        for key in keys {
+            tx.prepare_cached("DELETE FROM config WHERE key = ?1")?
+                .execute([key])?;
+        }
+        tx.commit()
+    })
+}
+
+pub fn set_model(app: &tauri::AppHandle, path: &str) -> Result<(), String> {
+    with_db(app, |conn| {
+        let tx = conn.transaction()?;
+        tx.prepare_cached(
+            "INSERT OR REPLACE INTO config (key, value) VALUES ('current_model', ?1)",
+        )?
+        .execute([path])?;
+        tx.prepare_cached("INSERT OR IGNORE INTO models (path) VALUES (?1)")?
+            .execute([path])?;
+        tx.commit()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_db_open() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("rive2d.db");
+        let db = Db::open(&path).unwrap();
+        let conn = db.0.lock().unwrap();
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        assert_eq!(migrations::user_version(&conn).unwrap(), migrations::LATEST);
+        assert_eq!(current_model(&conn).unwrap(), None);
+        assert!(setting_values(&conn).unwrap().is_empty());
+        drop(conn);
+
+        // A newer schema is refused rather than opened
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", migrations::LATEST + 1)
+            .unwrap();
+        assert!(Db::open(&path).is_err());
+    }
+
+    #[test]
+    fn test_stored_setting() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = Db::open(&tmp.path().join("rive2d.db")).unwrap();
+        let conn = db.0.lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO models (path) VALUES ('/a.model3.json'), ('/b.model3.json');
+            INSERT INTO config VALUES
+                ('current_model', '/a.model3.json'),
+                ('tap_motion', 'false'),
+                ('model_scale', '0.5'),
+                ('show_border', 'true');
+            INSERT INTO model_settings (model_id, key, value)
+                SELECT id, 'tap_motion', 'true' FROM models WHERE path = '/a.model3.json';
+            INSERT INTO model_settings (model_id, key, value)
+                SELECT id, 'model_scale', '2' FROM models WHERE path = '/b.model3.json';",
+        )
+        .unwrap();
+        let get = |key| stored_setting(&conn, key).unwrap();
+        // The current model's override wins over the global value
+        assert_eq!(get("tap_motion").as_deref(), Some("true"));
+        // Other models' overrides do not apply
+        assert_eq!(get("model_scale").as_deref(), Some("0.5"));
+        assert_eq!(get("show_border").as_deref(), Some("true"));
+        assert_eq!(get("lock_model"), None);
+    }
 }
@@ -0,0 +1,112 @@
+use crate::descriptor::ModelDescriptor;
+use std::path::{Component, Path, PathBuf};
+
+/// Resolve the target of a `change_cos <descriptor>` motion command.
+///
+/// `target` is relative to the directory of the loaded descriptor
+/// `current` and must stay inside the package of the library entry
+/// `model`: its `<hash>` directory for models installed into `models_dir`,
+/// otherwise the entry's own directory. The target has to be a loadable
+/// model descriptor.
+pub fn resolve(
+    models_dir: &Path,
+    model: &Path,
+    current: &Path,
+    target: &str,
+) -> Result<PathBuf, String> {
+    let target = target.trim();
+    if target.is_empty() {
+        return Err("No costume given".to_string());
+    }
+    if Path::new(target)
+        .components()
+        .any(|c| matches!(c, Component::RootDir | Component::Prefix(_)))
+    {
+        return Err(format!("Costume path must be relative: {}", target));
+    }
+
+    let dir = current.parent().ok_or("Invalid model path")?;
+    let path = dir
+        .join(target)
+        .canonicalize()
+        .map_err(|_| format!("Costume not found: {}", target))?;
+    let root = package_root(models_dir, model)
+        .canonicalize()
+        .map_err(|e| e.to_string())?;
+    if !path.starts_with(&root) {
+        return Err(format!("Costume outside the model package: {}", target));
+    }
+
+    ModelDescriptor::load(&path).map_err(|e| format!("Invalid costume {}: {}", target, e))?;
+    Ok(path)
+}
+
+/// Directory of the package holding the library entry `model`, see
+/// `resolve`.
+pub fn package_root(models_dir: &Path, model: &Path) -> PathBuf {
+    if let Ok(rel) = model.strip_prefix(models_dir) {
+        if let Some(Component::Normal(hash)) = rel.components().next() {
+            return models_dir.join(hash);
+        }
+    }
+    model.parent().unwrap_or(model).to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let tmp = tempfile::tempdir().unwrap();
+        let models = tmp.path().join("models");
+        let pkg = models.join("abc");
+        std::fs::create_dir_all(pkg.join("m")).unwrap();
+        for file in ["m/a.model3.json", "b.model3.json"] {
+            std::fs::write(pkg.join(file), r#"{"Version":3}"#).unwrap();
+        }
+        std::fs::write(pkg.join("broken.model3.json"), "{").unwrap();
+        std::fs::write(tmp.path().join("outside.model3.json"), r#"{"Version":3}"#).unwrap();
+        let model = pkg.join("m/a.model3.json");
+        (tmp, models, model)
+    }
+
+    #[test]
+    fn test_resolve_sibling_in_package() {
+        let (_tmp, models, model) = setup();
+        let path = resolve(&models, &model, &model, " ../b.model3.json ").unwrap();
+        assert_eq!(
+            path,
+            models.join("abc/b.model3.json").canonicalize().unwrap()
+        );
+
+        // Relative to the costume currently loaded, not the entry
+        let back = resolve(&models, &model, &path, "m/a.model3.json").unwrap();
+        assert_eq!(back, model.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_resolve_rejects_bad_targets() {
+        let (_tmp, models, model) = setup();
+        for target in [
+            "",
+            "../../../outside.model3.json",
+            "/etc/passwd",
+            "missing.model3.json",
+            "../broken.model3.json",
+        ] {
+            assert!(
+                resolve(&models, &model, &model, target).is_err(),
+                "{}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_outside_models_dir_uses_entry_dir() {
+        let (tmp, models, _) = setup();
+        let model = tmp.path().join("outside.model3.json");
+        assert!(resolve(&models, &model, &model, "outside.model3.json").is_ok());
+        assert!(resolve(&models, &model, &model, "models/abc/b.model3.json").is_ok());
+    }
+}
@@ -0,0 +1,273 @@
+use crate::model2::Model2;
+use crate::model3::Model3;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Cubism generation of a model descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CubismVersion {
+    /// `.model.json` + `.moc`
+    Cubism2,
+    /// `.model3.json` + `.moc3` (Cubism 3 and 4)
+    Cubism3,
+}
+
+/// Version-agnostic view of a model descriptor.
+///
+/// All file paths are as written in the descriptor, i.e. relative to the
+/// descriptor's directory.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelDescriptor {
+    pub version: CubismVersion,
+    pub name: Option<String>,
+    pub moc: Option<String>,
+    pub textures: Vec<String>,
+    pub physics: Option<String>,
+    pub pose: Option<String>,
+    pub expressions: Vec<ExpressionRef>,
+    pub motions: BTreeMap<String, Vec<MotionRef>>,
+    pub hit_areas: Vec<HitAreaRef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExpressionRef {
+    pub name: Option<String>,
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MotionRef {
+    pub file: Option<String>,
+    pub name: Option<String>,
+    pub sound: Option<String>,
+    /// Milliseconds
+    pub fade_in: Option<f64>,
+    /// Milliseconds
+    pub fade_out: Option<f64>,
+    pub next_mtn: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HitAreaRef {
+    pub name: String,
+    pub id: String,
+    /// Motion reference triggered on tap (Cubism 3+ only)
+    pub motion: Option<String>,
+}
+
+/// Detect the Cubism generation from descriptor content.
+///
+/// Cubism 3/4 has "Version" and "FileReferences"; Cubism 2 has "model" and
+/// "textures".
+pub fn detect_version(json: &Value) -> CubismVersion {
+    if json.get("FileReferences").is_some() || json.get("Version").is_some() {
+        CubismVersion::Cubism3
+    } else {
+        CubismVersion::Cubism2
+    }
+}
+
+impl ModelDescriptor {
+    pub fn from_slice(data: &[u8]) -> Result<Self, String> {
+        let json: Value = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+        if !json.is_object() {
+            return Err("Model descriptor is not a JSON object".to_string());
+        }
+        // Values of the wrong type are left out, like a viewer would ignore
+        // them
+        match detect_version(&json) {
+            CubismVersion::Cubism3 => Ok(Self::from(&Model3::from_slice_lenient(data)?.0)),
+            CubismVersion::Cubism2 => Ok(Self::from(&Model2::from_slice_lenient(data)?.0)),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| e.to_string())?;
+        Self::from_slice(&data)
+    }
+
+    /// First non-empty texture path.
+    pub fn first_texture(&self) -> Option<&str> {
+        self.textures
+            .iter()
+            .map(String::as_str)
+            .find(|t| !t.is_empty())
+    }
+
+    /// Motion group names, sorted.
+    pub fn motion_groups(&self) -> Vec<String> {
+        self.motions.keys().cloned().collect()
+    }
+}
+
+impl From<&Model3> for ModelDescriptor {
+    fn from(m: &Model3) -> Self {
+        let fr = m.file_references.clone().unwrap_or_default();
+        let motions = m
+            .motions()
+            .into_iter()
+            .flatten()
+            .map(|(group, entries)| {
+                let entries = entries
+                    .iter()
+                    .map(|e| MotionRef {
+                        file: e.file.clone(),
+                        name: e.name.clone(),
+                        sound: e.sound.clone(),
+                        fade_in: e.fade_in.as_ref().and_then(|n| n.as_f64()),
+                        fade_out: e.fade_out.as_ref().and_then(|n| n.as_f64()),
+                        next_mtn: e.next_mtn.clone(),
+                    })
+                    .collect();
+                (group.clone(), entries)
+            })
+            .collect();
+
+        Self {
+            version: CubismVersion::Cubism3,
+            name: m.options.as_ref().and_then(|o| o.name.clone()),
+            moc: fr.moc,
+            textures: m.textures().to_vec(),
+            physics: fr.physics.or_else(|| fr.physics_v2.and_then(|p| p.file)),
+            pose: fr.pose,
+            expressions: fr
+                .expressions
+                .unwrap_or_default()
+                .into_iter()
+                .map(|e| ExpressionRef {
+                    name: e.name,
+                    file: e.file,
+                })
+                .collect(),
+            motions,
+            hit_areas: m
+                .hit_areas()
+                .iter()
+                .map(|a| HitAreaRef {
+                    name: a.name.clone().unwrap_or_default(),
+                    id: a.id.clone().unwrap_or_default(),
+                    motion: a.motion.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&Model2> for ModelDescriptor {
+    fn from(m: &Model2) -> Self {
+        let motions = m
+            .motions
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(group, entries)| {
+                let entries = entries
+                    .into_iter()
+                    .map(|e| MotionRef {
+                        file: e.file,
+                        name: None,
+                        sound: e.sound,
+                        fade_in: e.fade_in.and_then(|n| n.as_f64()),
+                        fade_out: e.fade_out.and_then(|n| n.as_f64()),
+                        next_mtn: None,
+                    })
+                    .collect();
+                (group, entries)
+            })
+            .collect();
+
+        Self {
+            version: CubismVersion::Cubism2,
+            name: m.name.clone(),
+            moc: m.model.clone(),
+            textures: m.textures.clone().unwrap_or_default(),
+            physics: m.physics.clone(),
+            pose: m.pose.clone(),
+            expressions: m
+                .expressions
+                .iter()
+                .flatten()
+                .map(|e| ExpressionRef {
+                    name: e.name.clone(),
+                    file: e.file.clone(),
+                })
+                .collect(),
+            motions,
+            hit_areas: m
+                .hit_areas
+                .iter()
+                .flatten()
+                .map(|a| HitAreaRef {
+                    name: a.name.clone().unwrap_or_default(),
+                    id: a.id.clone().unwrap_or_default(),
+                    motion: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL3: &str = include_str!("../tests/fixtures/full.model3.json");
+    const MODEL2: &str = include_str!("../tests/fixtures/sample.model.json");
+
+    #[test]
+    fn test_from_model3() {
+        let desc = ModelDescriptor::from_slice(MODEL3.as_bytes()).unwrap();
+        assert_eq!(desc.version, CubismVersion::Cubism3);
+        assert_eq!(desc.moc.as_deref(), Some("model.moc3"));
+        assert_eq!(desc.first_texture(), Some("texture_00.png"));
+        assert_eq!(desc.motion_groups(), ["Idle", "Menu", "Start", "Tap身体"]);
+        assert_eq!(desc.motions["Start"][0].fade_in, Some(500.0));
+        assert_eq!(desc.hit_areas[0].motion.as_deref(), Some("Tap身体"));
+        assert_eq!(desc.expressions.len(), 1);
+    }
+
+    #[test]
+    fn test_from_model2() {
+        let desc = ModelDescriptor::from_slice(MODEL2.as_bytes()).unwrap();
+        assert_eq!(desc.version, CubismVersion::Cubism2);
+        assert_eq!(desc.moc.as_deref(), Some("model.moc"));
+        assert_eq!(desc.first_texture(), Some("textures/texture_00.png"));
+        assert_eq!(desc.motion_groups(), ["idle", "tap_body"]);
+        assert_eq!(
+            desc.motions["tap_body"][0].sound.as_deref(),
+            Some("sounds/tap.mp3")
+        );
+        assert_eq!(desc.hit_areas[1].name, "body");
+        assert_eq!(desc.physics.as_deref(), Some("physics.json"));
+    }
+
+    #[test]
+    fn test_model2_wrong_types_ignored() {
+        let desc = ModelDescriptor::from_slice(
+            br#"{"name":1,"model":"a.moc","textures":[null,"b.png"],
+                "motions":{"idle":[{"file":"idle.mtn","fade_in":"500"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(desc.version, CubismVersion::Cubism2);
+        assert_eq!(desc.name, None);
+        assert_eq!(desc.moc.as_deref(), Some("a.moc"));
+        assert_eq!(desc.first_texture(), Some("b.png"));
+        assert_eq!(desc.motions["idle"][0].file.as_deref(), Some("idle.mtn"));
+        assert_eq!(desc.motions["idle"][0].fade_in, None);
+    }
+
+    #[test]
+    fn test_first_texture_skips_empty() {
+        let desc = ModelDescriptor::from_slice(br#"{"FileReferences":{"Textures":["","b.png"]}}"#)
+            .unwrap();
+        assert_eq!(desc.first_texture(), Some("b.png"));
+    }
+
+    #[test]
+    fn test_rejects_non_object() {
+        assert!(ModelDescriptor::from_slice(b"[1,2]").is_err());
+    }
+}
@@ -0,0 +1,414 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Config key prefix for per-fixup enable flags (`fixup:<id>` = "true"/"false").
+pub const SETTING_PREFIX: &str = "fixup:";
+
+/// A repair applied to model assets as they are served.
+pub trait Fixup: Send + Sync {
+    /// Stable identifier, used for the enable flag and in the log.
+    fn id(&self) -> &'static str;
+
+    /// Human-readable summary shown in settings.
+    fn description(&self) -> &'static str;
+
+    /// Cheap check on path and raw content before `apply` is attempted.
+    fn matches(&self, path: &Path, data: &[u8]) -> bool;
+
+    /// Transform `data`, returning `None` when nothing needed fixing.
+    fn apply(&self, data: &[u8]) -> Option<Fixed>;
+}
+
+/// Output of a fixup that changed something.
+pub struct Fixed {
+    pub data: Vec<u8>,
+    /// One entry per change, e.g. "removed 2 empty texture entries".
+    pub changes: Vec<String>,
+}
+
+/// A fixup that fired for a file.
+#[derive(Debug, Clone, Serialize)]
+pub struct Applied {
+    pub fixup: &'static str,
+    pub changes: Vec<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Registry
+// ---------------------------------------------------------------------------
+
+/// Ordered set of fixups; each one sees the output of the previous.
+pub struct Registry {
+    fixups: Vec<Box<dyn Fixup>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FixupInfo {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub enabled: bool,
+}
+
+impl Registry {
+    /// Registry with all built-in fixups.
+    pub fn builtin() -> Self {
+        Self {
+            fixups: vec![
+                Box::new(MissingGroups),
+                Box::new(EmptyTextures),
+                Box::new(MotionCounts),
+            ],
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.fixups.iter().any(|f| f.id() == id)
+    }
+
+    /// Describe every fixup with its enabled state from `flags`
+    /// (the `fixup:`-prefixed config entries).
+    pub fn list(&self, flags: &HashMap<String, String>) -> Vec<FixupInfo> {
+        self.fixups
+            .iter()
+            .map(|f| FixupInfo {
+                id: f.id(),
+                description: f.description(),
+                enabled: is_enabled(flags, f.id()),
+            })
+            .collect()
+    }
+
+    /// Run every enabled, matching fixup over `data`.
+    pub fn run(
+        &self,
+        path: &Path,
+        mut data: Vec<u8>,
+        flags: &HashMap<String, String>,
+    ) -> (Vec<u8>, Vec<Applied>) {
+        let mut applied = Vec::new();
+        for fixup in &self.fixups {
+            if !is_enabled(flags, fixup.id()) || !fixup.matches(path, &data) {
+                continue;
+            }
+            if let Some(fixed) = fixup.apply(&data) {
+                data = fixed.data;
+                applied.push(Applied {
+                    fixup: fixup.id(),
+                    changes: fixed.changes,
+                });
+            }
+        }
+        (data, applied)
+    }
+}
+
+/// Fixups are enabled unless explicitly set to "false".
+fn is_enabled(flags: &HashMap<String, String>, id: &str) -> bool {
+    flags
+        .get(&format!("{}{}", SETTING_PREFIX, id))
+        .map(|v| v != "false")
+        .unwrap_or(true)
+}
+
+// ---------------------------------------------------------------------------
+// Log
+// ---------------------------------------------------------------------------
+
+/// Record of a fixup that fired for a file of a model.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub file: String,
+    pub fixup: &'static str,
+    pub changes: Vec<String>,
+    pub at: String,
+}
+
+/// Which fixups fired for which model, keyed by model descriptor path.
+/// Only the latest entry per (file, fixup) is kept.
+#[derive(Default)]
+pub struct FixupLog(Mutex<HashMap<String, Vec<LogEntry>>>);
+
+impl FixupLog {
+    pub fn record(&self, model: &str, file: &str, applied: &[Applied]) {
+        if applied.is_empty() {
+            return;
+        }
+        let at = chrono::Local::now().to_rfc3339();
+        let mut log = self.0.lock().unwrap();
+        let entries = log.entry(model.to_string()).or_default();
+        for a in applied {
+            entries.retain(|e| !(e.file == file && e.fixup == a.fixup));
+            entries.push(LogEntry {
+                file: file.to_string(),
+                fixup: a.fixup,
+                changes: a.changes.clone(),
+                at: at.clone(),
+            });
+        }
+    }
+
+    pub fn get(&self, model: &str) -> Vec<LogEntry> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(model)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Built-in fixups
+// ---------------------------------------------------------------------------
+
+fn is_model3(path: &Path) -> bool {
+    path.to_str()
+        .map(|p| p.ends_with(".model3.json"))
+        .unwrap_or(false)
+}
+
+fn to_json(json: &Value) -> Option<Vec<u8>> {
+    serde_json::to_vec(json).ok()
+}
+
+/// Add the missing "Groups" field required by the Cubism SDK.
+struct MissingGroups;
+
+impl Fixup for MissingGroups {
+    fn id(&self) -> &'static str {
+        "model3_groups"
+    }
+
+    fn description(&self) -> &'static str {
+        "Add missing Groups field to model3.json"
+    }
+
+    fn matches(&self, path: &Path, _data: &[u8]) -> bool {
+        is_model3(path)
+    }
+
+    // Edits the JSON as is rather than through `Model3`, so fields of the
+    // wrong type elsewhere in the file neither block the fix nor get lost
+    fn apply(&self, data: &[u8]) -> Option<Fixed> {
+        let mut json: Value = serde_json::from_slice(data).ok()?;
+        if json.get("FileReferences").is_none() || json.get("Groups").is_some() {
+            return None;
+        }
+        json["Groups"] = serde_json::json!([]);
+        Some(Fixed {
+            data: to_json(&json)?,
+            changes: vec!["added empty Groups".to_string()],
+        })
+    }
+}
+
+/// Remove empty texture entries — LPK extraction can leave empty strings
+/// (or nulls) which cause Assets.load("") to return a plain object instead of a
+/// Texture, crashing the renderer.
+struct EmptyTextures;
+
+impl Fixup for EmptyTextures {
+    fn id(&self) -> &'static str {
+        "model3_empty_textures"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove empty texture entries from model3.json"
+    }
+
+    fn matches(&self, path: &Path, _data: &[u8]) -> bool {
+        is_model3(path)
+    }
+
+    fn apply(&self, data: &[u8]) -> Option<Fixed> {
+        let mut json: Value = serde_json::from_slice(data).ok()?;
+        let textures = json
+            .pointer_mut("/FileReferences/Textures")
+            .and_then(|v| v.as_array_mut())?;
+        let before = textures.len();
+        textures.retain(|v| !v.is_null() && v.as_str() != Some(""));
+        let removed = before - textures.len();
+        if removed == 0 {
+            return None;
+        }
+        Some(Fixed {
+            data: to_json(&json)?,
+            changes: vec![format!("removed {} empty texture entries", removed)],
+        })
+    }
+}
+
+/// Fix incorrect TotalPointCount/TotalSegmentCount in motion3.json.
+///
+/// LPK-extracted motions have hashed filenames (.json, not .motion3.json),
+/// so detect by content structure rather than extension.
+struct MotionCounts;
+
+impl Fixup for MotionCounts {
+    fn id(&self) -> &'static str {
+        "motion3_counts"
+    }
+
+    fn description(&self) -> &'static str {
+        "Recompute TotalPointCount/TotalSegmentCount in motion3.json"
+    }
+
+    fn matches(&self, path: &Path, data: &[u8]) -> bool {
+        path.extension().and_then(|e| e.to_str()) == Some("json")
+            && data.windows(15).any(|w| w == b"TotalPointCount")
+    }
+
+    fn apply(&self, data: &[u8]) -> Option<Fixed> {
+        let mut json: Value = serde_json::from_slice(data).ok()?;
+        json.get("Meta").and_then(|m| m.get("TotalPointCount"))?;
+        let (points, segments) = count_motion_points(json.get("Curves")?.as_array()?);
+
+        let meta = &json["Meta"];
+        let old_points = meta.get("TotalPointCount").and_then(|v| v.as_u64());
+        let old_segments = meta.get("TotalSegmentCount").and_then(|v| v.as_u64());
+        let mut changes = Vec::new();
+        if old_points != Some(points) {
+            changes.push(format!(
+                "TotalPointCount {} -> {}",
+                old_points.map_or("?".to_string(), |v| v.to_string()),
+                points
+            ));
+        }
+        if old_segments != Some(segments) {
+            changes.push(format!(
+                "TotalSegmentCount {} -> {}",
+                old_segments.map_or("?".to_string(), |v| v.to_string()),
+                segments
+            ));
+        }
+        if changes.is_empty() {
+            return None;
+        }
+
+        json["Meta"]["TotalPointCount"] = serde_json::json!(points);
+        json["Meta"]["TotalSegmentCount"] = serde_json::json!(segments);
+        Some(Fixed {
+            data: to_json(&json)?,
+            changes,
+        })
+    }
+}
+
+/// Count points and segments of motion3.json curves as the Cubism SDK expects.
+pub fn count_motion_points(curves: &[Value]) -> (u64, u64) {
+    let mut total_points: u64 = 0;
+    let mut total_segments: u64 = 0;
+
+    for curve in curves {
+        if let Some(segs) = curve.get("Segments").and_then(|s| s.as_array()) {
+            if segs.len() < 2 {
+                continue;
+            }
+            total_points += 1; // initial point (time, value)
+            let mut i = 2;
+            while i < segs.len() {
+                let seg_type = segs[i].as_f64().unwrap_or(-1.0) as i64;
+                match seg_type {
+                    0 | 2 | 3 => {
+                        // Linear / Stepped / InvStepped
+                        total_points += 1;
+                        total_segments += 1;
+                        i += 3;
+                    }
+                    1 => {
+                        // Bezier
+                        total_points += 3;
+                        total_segments += 1;
+                        i += 7;
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    (total_points, total_segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(path: &str, data: &str, flags: &HashMap<String, String>) -> (Value, Vec<Applied>) {
+        let (out, applied) =
+            Registry::builtin().run(Path::new(path), data.as_bytes().to_vec(), flags);
+        (serde_json::from_slice(&out).unwrap(), applied)
+    }
+
+    #[test]
+    fn test_model3_fixups() {
+        let (json, applied) = run(
+            "/m/a.model3.json",
+            r#"{"FileReferences":{"Textures":["a.png","",null,""]},"LipSync":1}"#,
+            &HashMap::new(),
+        );
+        assert_eq!(json["Groups"], serde_json::json!([]));
+        assert_eq!(
+            json["FileReferences"]["Textures"],
+            serde_json::json!(["a.png"])
+        );
+        // Fields of the wrong type do not block the fixes and are kept
+        assert_eq!(json["LipSync"], 1);
+        let ids: Vec<_> = applied.iter().map(|a| a.fixup).collect();
+        assert_eq!(ids, ["model3_groups", "model3_empty_textures"]);
+        assert_eq!(applied[1].changes, ["removed 3 empty texture entries"]);
+    }
+
+    #[test]
+    fn test_clean_model3_untouched() {
+        let src = r#"{"FileReferences":{"Textures":["a.png"]},"Groups":[]}"#;
+        let (out, applied) = Registry::builtin().run(
+            Path::new("/m/a.model3.json"),
+            src.as_bytes().to_vec(),
+            &HashMap::new(),
+        );
+        assert!(applied.is_empty());
+        assert_eq!(out, src.as_bytes());
+    }
+
+    #[test]
+    fn test_motion_counts() {
+        // Linear segment + bezier segment: 1 + 1 + 3 points, 2 segments
+        let motion = r#"{"Meta":{"TotalPointCount":99,"TotalSegmentCount":2},
+            "Curves":[{"Segments":[0,0, 0,1,1, 1,1.5,1,2,1,3,1]}]}"#;
+        let (json, applied) = run("/m/8f3a.json", motion, &HashMap::new());
+        assert_eq!(json["Meta"]["TotalPointCount"], 5);
+        assert_eq!(json["Meta"]["TotalSegmentCount"], 2);
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].changes, ["TotalPointCount 99 -> 5"]);
+    }
+
+    #[test]
+    fn test_disabled_fixup_skipped() {
+        let flags = HashMap::from([(
+            format!("{}model3_groups", SETTING_PREFIX),
+            "false".to_string(),
+        )]);
+        let (json, applied) = run("/m/a.model3.json", r#"{"FileReferences":{}}"#, &flags);
+        assert!(json.get("Groups").is_none());
+        assert!(applied.is_empty());
+        assert!(!Registry::builtin().list(&flags)[0].enabled);
+    }
+
+    #[test]
+    fn test_log_keeps_latest_per_file() {
+        let log = FixupLog::default();
+        let applied = [Applied {
+            fixup: "motion3_counts",
+            changes: vec!["x".to_string()],
+        }];
+        log.record("/m/a.model3.json", "/m/1.json", &applied);
+        log.record("/m/a.model3.json", "/m/1.json", &applied);
+        log.record("/m/a.model3.json", "/m/2.json", &applied);
+        assert_eq!(log.get("/m/a.model3.json").len(), 2);
+        assert!(log.get("/m/b.model3.json").is_empty());
+    }
+}
@@ -0,0 +1,654 @@
+use crate::descriptor::ModelDescriptor;
+use crate::lpk::{self, Costume};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Prefix of in-progress extraction directories inside the models dir.
+const STAGING_PREFIX: &str = ".staging-";
+
+static STAGING_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Archive extensions imported like LPKs; plain zips extract as regular LPKs.
+pub const ARCHIVE_EXTENSIONS: &[&str] = &["lpk", "zip"];
+
+/// Something an import can bring into the library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportSource {
+    /// `.lpk` or `.zip` archive, installed into the models dir
+    Archive(PathBuf),
+    /// Descriptor of an unpacked model, registered in place
+    Model(PathBuf),
+}
+
+impl ImportSource {
+    pub fn path(&self) -> &Path {
+        match self {
+            ImportSource::Archive(p) | ImportSource::Model(p) => p,
+        }
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| ARCHIVE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Collect importable archives and unpacked models below `dir`, at most
+/// `max_depth` directories deep, in path order.
+///
+/// A directory holding model descriptors is a model folder: its distinct
+/// models are imported (see `lpk::distinct_models`) along with archives next
+/// to them, and the folder is not searched further, so motion or texture
+/// subdirectories are never mistaken for models.
+pub fn collect_sources(dir: &Path, max_depth: u32) -> Vec<ImportSource> {
+    let mut out = Vec::new();
+    collect_sources_in(dir, 0, max_depth, &mut out);
+    out
+}
+
+fn collect_sources_in(dir: &Path, depth: u32, max_depth: u32, out: &mut Vec<ImportSource>) {
+    if depth > max_depth {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+
+    let is_model_folder = paths.iter().any(|p| {
+        p.is_file()
+            && p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(lpk::is_model_json)
+    });
+    if is_model_folder {
+        let candidates = lpk::find_model_candidates(dir)
+            .into_iter()
+            .filter(|c| c.depth == 0)
+            .collect();
+        let mut found: Vec<_> = lpk::distinct_models(candidates)
+            .into_iter()
+            .map(|c| ImportSource::Model(c.path.into()))
+            .chain(
+                paths
+                    .into_iter()
+                    .filter(|p| p.is_file() && is_archive(p))
+                    .map(ImportSource::Archive),
+            )
+            .collect();
+        found.sort_by(|a, b| a.path().cmp(b.path()));
+        out.extend(found);
+        return;
+    }
+
+    for path in paths {
+        if path.is_dir() {
+            collect_sources_in(&path, depth + 1, max_depth, out);
+        } else if is_archive(&path) {
+            out.push(ImportSource::Archive(path));
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Dedupe
+// ---------------------------------------------------------------------------
+
+/// Prefix of the error returned for an import that duplicates a registered
+/// model, followed by that model's path.
+pub const DUPLICATE_ERROR: &str = "Duplicate of an existing model";
+
+/// What to do when an import has the same content as a registered model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnDuplicate {
+    /// Fail with `DUPLICATE_ERROR` so the user can decide
+    #[default]
+    Ask,
+    /// Register the new model in place of the existing one
+    Replace,
+    /// Register the new model alongside the existing one
+    KeepBoth,
+}
+
+pub fn file_md5(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut ctx = md5::Context::new();
+    std::io::copy(&mut file, &mut ctx).map_err(|e| e.to_string())?;
+    Ok(format!("{:x}", ctx.compute()))
+}
+
+/// Content fingerprint of the model at `model`: an MD5 over the sorted MD5s
+/// of its moc and textures. Repackaged, renamed or unpacked copies of the
+/// same model share it.
+pub fn asset_hash(model: &Path) -> Result<String, String> {
+    let desc = ModelDescriptor::load(model)?;
+    let dir = model.parent().ok_or("Invalid model path")?;
+    let mut hashes = desc
+        .moc
+        .iter()
+        .chain(&desc.textures)
+        .filter(|f| !f.is_empty())
+        .map(|f| file_md5(&dir.join(f)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if hashes.is_empty() {
+        return Err("Model has no moc or textures".to_string());
+    }
+    hashes.sort();
+    hashes.dedup();
+    Ok(format!("{:x}", md5::compute(hashes.join("\n"))))
+}
+
+// ---------------------------------------------------------------------------
+// Installation
+// ---------------------------------------------------------------------------
+
+/// Install an LPK into `models_dir/<dir_name>` and register it.
+///
+/// The archive is extracted into a staging directory next to the
+/// destination and renamed into place only once every costume produced a
+/// loadable model descriptor. `register` is then called with the installed
+/// models, each as its costumes with the default first (see
+/// `lpk::extract_lpk`); if it fails, the installed directory is removed
+/// again, so either both the files and the registration exist or neither
+/// does. `in_use` tells whether a directory holds registered models, which
+/// are never overwritten.
+pub fn install_lpk(
+    models_dir: &Path,
+    lpk_path: &str,
+    dir_name: &str,
+    limits: &lpk::ExtractLimits,
+    stm: &lpk::StmConfigSources,
+    in_use: impl FnOnce(&Path) -> Result<bool, String>,
+    register: impl FnOnce(&[Vec<Costume>]) -> Result<(), String>,
+) -> Result<Vec<Vec<Costume>>, String> {
+    std::fs::create_dir_all(models_dir).map_err(|e| e.to_string())?;
+    let staging = models_dir.join(format!(
+        "{}{}-{}-{}",
+        STAGING_PREFIX,
+        dir_name,
+        std::process::id(),
+        STAGING_SEQ.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    // extract_lpk removes the staging dir itself when extraction fails
+    let models = lpk::extract_lpk(&staging, lpk_path, limits, stm)?;
+    let dest = models_dir.join(dir_name);
+    let installed = match promote(&staging, &dest, &models, in_use) {
+        Ok(installed) => installed,
+        Err(e) => {
+            std::fs::remove_dir_all(&staging).ok();
+            return Err(e);
+        }
+    };
+
+    if let Err(e) = register(&installed) {
+        std::fs::remove_dir_all(&dest).ok();
+        return Err(e);
+    }
+    Ok(installed)
+}
+
+/// Check the extracted models and move `staging` to `dest`, returning
+/// the models with their paths inside `dest`.
+fn promote(
+    staging: &Path,
+    dest: &Path,
+    models: &[Vec<Costume>],
+    in_use: impl FnOnce(&Path) -> Result<bool, String>,
+) -> Result<Vec<Vec<Costume>>, String> {
+    let rebase = |path: &str| -> Result<String, String> {
+        let rel = Path::new(path)
+            .strip_prefix(staging)
+            .map_err(|_| "Model file outside extraction directory".to_string())?;
+        Ok(dest.join(rel).to_string_lossy().to_string())
+    };
+    let mut installed = Vec::with_capacity(models.len());
+    for costumes in models {
+        let mut model = Vec::with_capacity(costumes.len());
+        for costume in costumes {
+            ModelDescriptor::load(Path::new(&costume.path))
+                .map_err(|e| format!("Invalid model descriptor: {}", e))?;
+            model.push(Costume {
+                path: rebase(&costume.path)?,
+                name: costume.name.clone(),
+                avatar: costume.avatar.as_deref().map(rebase).transpose()?,
+            });
+        }
+        installed.push(model);
+    }
+
+    // A directory without a registered model is left over from an
+    // interrupted import; one with a registered model is someone's install.
+    if dest.exists() {
+        if in_use(dest)? {
+            return Err("Model already imported".to_string());
+        }
+        std::fs::remove_dir_all(dest).map_err(|e| e.to_string())?;
+    }
+    std::fs::rename(staging, dest).map_err(|e| e.to_string())?;
+    Ok(installed)
+}
+
+/// Remove staging directories left behind by a crash mid-import.
+pub fn clean_staging(models_dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(models_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry
+            .file_name()
+            .to_str()
+            .is_some_and(|n| n.starts_with(STAGING_PREFIX))
+        {
+            std::fs::remove_dir_all(entry.path()).ok();
+        }
+    }
+}
+
+/// Run `job` over `items` on at most `workers` threads, in no particular
+/// order. Items not yet started when `cancel` is set are skipped; their
+/// indices are returned.
+pub fn run_pool<T: Sync>(
+    items: &[T],
+    workers: usize,
+    cancel: &AtomicBool,
+    job: impl Fn(usize, &T) + Sync,
+) -> Vec<usize> {
+    let next = AtomicUsize::new(0);
+    let skipped = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(i) else {
+                    break;
+                };
+                if cancel.load(Ordering::Relaxed) {
+                    skipped.lock().unwrap().push(i);
+                    continue;
+                }
+                job(i, item);
+            });
+        }
+    });
+    let mut skipped = skipped.into_inner().unwrap();
+    skipped.sort_unstable();
+    skipped
+}
+
+/// Archive hashes being imported right now, so concurrent imports of
+/// identical files do not install into the same directory.
+#[derive(Default)]
+pub struct InFlight(Mutex<HashSet<String>>);
+
+impl InFlight {
+    /// Claim `hash`, or `None` if another import holds it. The claim is
+    /// released when the guard is dropped.
+    pub fn claim(&self, hash: &str) -> Option<InFlightGuard<'_>> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(hash.to_string())
+            .then(|| InFlightGuard {
+                set: self,
+                hash: hash.to_string(),
+            })
+    }
+}
+
+pub struct InFlightGuard<'a> {
+    set: &'a InFlight,
+    hash: String,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.set.0.lock().unwrap().remove(&self.hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lpk::write_zip;
+
+    fn dir_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_install_into_hash_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let models = tmp.path().join("models");
+        let lpk = tmp.path().join("model.lpk");
+        write_zip(&lpk, &[("m/a.model3.json", br#"{"Version":3}"#)]);
+
+        let mut registered = Vec::new();
+        let installed = install_lpk(
+            &models,
+            lpk.to_str().unwrap(),
+            "abc",
+            &lpk::ExtractLimits::default(),
+            &lpk::StmConfigSources::default(),
+            |_| Ok(false),
+            |m| {
+                registered = m.to_vec();
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(installed.len(), 1);
+        assert_eq!(
+            Path::new(&installed[0][0].path),
+            models.join("abc/m/a.model3.json")
+        );
+        assert_eq!(registered, installed);
+        assert_eq!(dir_names(&models), ["abc"]);
+    }
+
+    #[test]
+    fn test_install_costumes_into_dest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let models = tmp.path().join("models");
+        let lpk = tmp.path().join("model.lpk");
+        let a = "00000000000000000000000000000001.bin3";
+        let b = "00000000000000000000000000000002.bin3";
+        let avatar = "00000000000000000000000000000003.bin";
+        let manifest = format!(
+            r#"{{"type":"STD_1_0","name":"M","list":[{{"avatar":"{avatar}",
+                "costume":[{{"name":"A","path":"{a}"}},{{"name":"B","path":"{b}"}}]}}]}}"#
+        );
+        write_zip(
+            &lpk,
+            &[
+                ("config.mlve", manifest.as_bytes()),
+                (a, br#"{"Version":3}"#),
+                (b, br#"{"Version":3}"#),
+                (avatar, b"\x89PNG"),
+            ],
+        );
+
+        let installed = install_lpk(
+            &models,
+            lpk.to_str().unwrap(),
+            "abc",
+            &lpk::ExtractLimits::default(),
+            &lpk::StmConfigSources::default(),
+            |_| Ok(false),
+            |m| {
+                assert_eq!(m.len(), 1);
+                assert_eq!(m[0].len(), 2);
+                Ok(())
+            },
+        )
+        .unwrap();
+        for (costume, file) in installed[0]
+            .iter()
+            .zip(["M_A.model3.json", "M_B.model3.json"])
+        {
+            assert_eq!(Path::new(&costume.path), models.join("abc").join(file));
+            assert!(Path::new(&costume.path).exists());
+            let avatar = costume.avatar.as_deref().unwrap();
+            assert!(avatar.starts_with(models.join("abc").to_str().unwrap()));
+        }
+        assert_eq!(dir_names(&models), ["abc"]);
+    }
+
+    #[test]
+    fn test_same_stem_does_not_overwrite() {
+        let tmp = tempfile::tempdir().unwrap();
+        let models = tmp.path().join("models");
+        let limits = lpk::ExtractLimits::default();
+        let stm = lpk::StmConfigSources::default();
+        for (hash, sub) in [("h1", "a"), ("h2", "b")] {
+            std::fs::create_dir_all(tmp.path().join(sub)).unwrap();
+            let lpk = tmp.path().join(sub).join("model.lpk");
+            write_zip(&lpk, &[("x.model3.json", b"{}")]);
+            install_lpk(
+                &models,
+                lpk.to_str().unwrap(),
+                hash,
+                &limits,
+                &stm,
+                |_| Ok(false),
+                |_| Ok(()),
+            )
+            .unwrap();
+        }
+        assert_eq!(dir_names(&models), ["h1", "h2"]);
+    }
+
+    #[test]
+    fn test_registered_dest_is_not_overwritten() {
+        let tmp = tempfile::tempdir().unwrap();
+        let models = tmp.path().join("models");
+        let installed = models.join("abc").join("a.model3.json");
+        std::fs::create_dir_all(installed.parent().unwrap()).unwrap();
+        std::fs::write(&installed, "{}").unwrap();
+        let lpk = tmp.path().join("model.lpk");
+        write_zip(&lpk, &[("b.model3.json", b"{}")]);
+
+        let limits = lpk::ExtractLimits::default();
+        let stm = lpk::StmConfigSources::default();
+        let install = |in_use: bool| {
+            install_lpk(
+                &models,
+                lpk.to_str().unwrap(),
+                "abc",
+                &limits,
+                &stm,
+                |dir| Ok(in_use && dir == models.join("abc")),
+                |_| Ok(()),
+            )
+        };
+        assert!(install(true).is_err());
+        assert!(installed.exists());
+        assert_eq!(dir_names(&models), ["abc"]);
+
+        // Left over from an interrupted import: replaced
+        install(false).unwrap();
+        assert!(!installed.exists());
+        assert_eq!(dir_names(&models.join("abc")), ["b.model3.json"]);
+    }
+
+    #[test]
+    fn test_failed_registration_rolls_back() {
+        let tmp = tempfile::tempdir().unwrap();
+        let models = tmp.path().join("models");
+        let lpk = tmp.path().join("model.lpk");
+        write_zip(&lpk, &[("a.model3.json", b"{}")]);
+
+        let err = install_lpk(
+            &models,
+            lpk.to_str().unwrap(),
+            "abc",
+            &lpk::ExtractLimits::default(),
+            &lpk::StmConfigSources::default(),
+            |_| Ok(false),
+            |_| Err("database is locked".to_string()),
+        )
+        .unwrap_err();
+        assert_eq!(err, "database is locked");
+        assert!(dir_names(&models).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_model_leaves_nothing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let models = tmp.path().join("models");
+        let limits = lpk::ExtractLimits::default();
+        let stm = lpk::StmConfigSources::default();
+
+        let broken = tmp.path().join("broken.lpk");
+        write_zip(&broken, &[("a.model3.json", b"{not json")]);
+        assert!(install_lpk(
+            &models,
+            broken.to_str().unwrap(),
+            "h1",
+            &limits,
+            &stm,
+            |_| Ok(false),
+            |_| Ok(())
+        )
+        .is_err());
+
+        let empty = tmp.path().join("empty.lpk");
+        write_zip(&empty, &[("readme.txt", b"hi")]);
+        assert!(install_lpk(
+            &models,
+            empty.to_str().unwrap(),
+            "h2",
+            &limits,
+            &stm,
+            |_| Ok(false),
+            |_| Ok(())
+        )
+        .is_err());
+
+        assert!(dir_names(&models).is_empty());
+    }
+
+    #[test]
+    fn test_run_pool_bounds_workers() {
+        let items: Vec<u32> = (0..50).collect();
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let done = Mutex::new(Vec::new());
+        let skipped = run_pool(&items, 3, &AtomicBool::new(false), |_, item| {
+            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            done.lock().unwrap().push(*item);
+            active.fetch_sub(1, Ordering::SeqCst);
+        });
+        assert!(skipped.is_empty());
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        let mut done = done.into_inner().unwrap();
+        done.sort_unstable();
+        assert_eq!(done, items);
+    }
+
+    #[test]
+    fn test_run_pool_cancel() {
+        let items: Vec<u32> = (0..20).collect();
+        let cancel = AtomicBool::new(false);
+        let ran = AtomicUsize::new(0);
+        let skipped = run_pool(&items, 1, &cancel, |i, _| {
+            ran.fetch_add(1, Ordering::SeqCst);
+            if i == 4 {
+                cancel.store(true, Ordering::SeqCst);
+            }
+        });
+        assert_eq!(ran.load(Ordering::SeqCst), 5);
+        assert_eq!(skipped, (5..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_in_flight_claim() {
+        let in_flight = InFlight::default();
+        let guard = in_flight.claim("abc").unwrap();
+        assert!(in_flight.claim("abc").is_none());
+        assert!(in_flight.claim("def").is_some());
+        drop(guard);
+        assert!(in_flight.claim("abc").is_some());
+    }
+
+    #[test]
+    fn test_collect_sources() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        for dir in ["a/motions", "b/sub", "c/d/e/f/g/h"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "1.lpk",
+            "2.ZIP",
+            "notes.txt",
+            "a/y.model.json",
+            "a/extra.lpk",
+            "a/motions/m.model3.json",
+            "b/sub/3.zip",
+            "c/d/e/f/g/h/deep.lpk",
+        ] {
+            std::fs::write(root.join(file), "{}").unwrap();
+        }
+        // A leftover Cubism 2 descriptor next to a Cubism 3 one is ignored
+        std::fs::write(root.join("a/x.model3.json"), r#"{"Version":3}"#).unwrap();
+
+        let sources = collect_sources(root, 5);
+        assert_eq!(
+            sources,
+            [
+                ImportSource::Archive(root.join("1.lpk")),
+                ImportSource::Archive(root.join("2.ZIP")),
+                ImportSource::Archive(root.join("a/extra.lpk")),
+                ImportSource::Model(root.join("a/x.model3.json")),
+                ImportSource::Archive(root.join("b/sub/3.zip")),
+            ]
+        );
+    }
+
+    fn write_model(dir: &Path, name: &str, moc: &str, textures: &[(&str, &str)]) -> PathBuf {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(format!("{}.moc3", name)), moc).unwrap();
+        for (file, data) in textures {
+            std::fs::write(dir.join(file), data).unwrap();
+        }
+        let files: Vec<_> = textures.iter().map(|(f, _)| *f).collect();
+        let model = dir.join(format!("{}.model3.json", name));
+        let json = serde_json::json!({
+            "Version": 3,
+            "FileReferences": {"Moc": format!("{}.moc3", name), "Textures": files},
+        });
+        std::fs::write(&model, json.to_string()).unwrap();
+        model
+    }
+
+    #[test]
+    fn test_asset_hash_ignores_names_and_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let a = write_model(
+            &tmp.path().join("a"),
+            "a",
+            "moc",
+            &[("t0.png", "red"), ("t1.png", "blue")],
+        );
+        let b = write_model(
+            &tmp.path().join("b"),
+            "renamed",
+            "moc",
+            &[("x.png", "blue"), ("y.png", "red")],
+        );
+        let c = write_model(
+            &tmp.path().join("c"),
+            "a",
+            "moc",
+            &[("t0.png", "red"), ("t1.png", "green")],
+        );
+        assert_eq!(asset_hash(&a).unwrap(), asset_hash(&b).unwrap());
+        assert_ne!(asset_hash(&a).unwrap(), asset_hash(&c).unwrap());
+
+        std::fs::remove_file(tmp.path().join("c/t1.png")).unwrap();
+        assert!(asset_hash(&c).is_err());
+    }
+
+    #[test]
+    fn test_clean_staging() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join(".staging-abc-1-0/m")).unwrap();
+        std::fs::create_dir_all(tmp.path().join("abc")).unwrap();
+        clean_staging(tmp.path());
+        assert_eq!(dir_names(tmp.path()), ["abc"]);
+    }
+}
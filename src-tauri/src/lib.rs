@@ -1,11 +1,26 @@
 #[cfg(target_os = "linux")]
 mod layer_shell;
 
+mod cache;
 mod config;
+mod costume;
+mod descriptor;
+mod fixups;
+mod import;
 mod lpk;
+mod migrations;
+mod model2;
+mod model3;
+mod pack;
+mod preview;
+mod protocol;
+mod repair;
+mod settings;
 mod tray;
+mod validate;
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use tauri::{Emitter, Manager};
 
@@ -18,142 +33,34 @@ pub fn run() {
     let mut builder = tauri::Builder::default()
         .plugin(tauri_plugin_single_instance::init(|_app, _args, _cwd| {}))
         .plugin(tauri_plugin_dialog::init())
-        .register_uri_scheme_protocol("model", |_ctx, request| {
-            // Serve model files from the filesystem via model:// protocol
-            let uri = request.uri().to_string();
-            // URI format: model://localhost/<absolute-path>
-            let path = uri
-                .strip_prefix("model://localhost/")
-                .or_else(|| uri.strip_prefix("model://localhost"))
-                .unwrap_or("");
-            let path = percent_encoding::percent_decode_str(path)
-                .decode_utf8_lossy()
-                .to_string();
-
-            let file_path = std::path::Path::new(&path);
-            match std::fs::read(file_path) {
-                Ok(mut data) => {
-                    let mime = match file_path.extension().and_then(|e| e.to_str()) {
-                        Some("json") => "application/json",
-                        Some("moc3") | Some("moc") => "application/octet-stream",
-                        Some("png") => "image/png",
-                        Some("jpg") | Some("jpeg") => "image/jpeg",
-                        Some("wav") => "audio/wav",
-                        Some("ogg") => "audio/ogg",
-                        Some("mp3") => "audio/mpeg",
-                        Some("mtn") => "text/plain",
-                        _ => "application/octet-stream",
-                    };
-
-                    // Patch model3.json
-                    if path.ends_with(".model3.json") {
-                        if let Ok(text) = std::str::from_utf8(&data) {
-                            if let Ok(mut json) = serde_json::from_str::<serde_json::Value>(text) {
-                                let mut patched_any = false;
-
-                                // Add missing "Groups" field required by Cubism SDK
-                                if json.get("FileReferences").is_some() && json.get("Groups").is_none() {
-                                    json["Groups"] = serde_json::json!([]);
-                                    patched_any = true;
-                                }
-
-                                // Remove empty texture entries — LPK extraction can leave
-                                // empty strings which cause Assets.load("") to return a
-                                // plain object instead of a Texture, crashing the renderer.
-                                if let Some(textures) = json.pointer_mut("/FileReferences/Textures")
-                                    .and_then(|v| v.as_array_mut())
-                                {
-                                    let before = textures.len();
-                                    textures.retain(|v| {
-                                        v.as_str().map_or(true, |s| !s.is_empty())
-                                    });
-                                    if textures.len() != before {
-                                        patched_any = true;
-                                    }
-                                }
-
-                                if patched_any {
-                                    if let Ok(out) = serde_json::to_vec(&json) {
-                                        data = out;
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    // Patch motion3.json: fix incorrect TotalPointCount/TotalSegmentCount
-                    // LPK-extracted motions have hashed filenames (.json, not .motion3.json),
-                    // so detect by content structure rather than extension.
-                    if mime == "application/json" {
-                        if let Ok(text) = std::str::from_utf8(&data) {
-                            if let Ok(mut json) = serde_json::from_str::<serde_json::Value>(text) {
-                                if json.get("Meta").and_then(|m| m.get("TotalPointCount")).is_some()
-                                    && json.get("Curves").and_then(|c| c.as_array()).is_some()
-                                {
-                                    let curves = json["Curves"].as_array().unwrap();
-                                    let mut total_points: u64 = 0;
-                                    let mut total_segments: u64 = 0;
-
-                                    for curve in curves {
-                                        if let Some(segs) = curve.get("Segments").and_then(|s| s.as_array()) {
-                                            if segs.len() < 2 { continue; }
-                                            total_points += 1; // initial point (time, value)
-                                            let mut i = 2;
-                                            while i < segs.len() {
-                                                let seg_type = segs[i].as_f64().unwrap_or(-1.0) as i64;
-                                                match seg_type {
-                                                    0 | 2 | 3 => { // Linear / Stepped / InvStepped
-                                                        total_points += 1;
-                                                        total_segments += 1;
-                                                        i += 3;
-                                                    }
-                                                    1 => { // Bezier
-                                                        total_points += 3;
-                                                        total_segments += 1;
-                                                        i += 7;
-                                                    }
-                                                    _ => break,
-                                                }
-                                            }
-                                        }
-                                    }
-
-                                    json["Meta"]["TotalPointCount"] = serde_json::json!(total_points);
-                                    json["Meta"]["TotalSegmentCount"] = serde_json::json!(total_segments);
-                                    if let Ok(patched) = serde_json::to_vec(&json) {
-                                        data = patched;
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    tauri::http::Response::builder()
-                        .header("Content-Type", mime)
-                        .header("Access-Control-Allow-Origin", "*")
-                        .header("Access-Control-Allow-Methods", "GET, OPTIONS")
-                        .header("Access-Control-Allow-Headers", "*")
-                        .header("Cross-Origin-Resource-Policy", "cross-origin")
-                        .body(data)
-                        .unwrap()
-                }
-                Err(_) => tauri::http::Response::builder()
-                    .status(404)
-                    .body(b"Not found".to_vec())
-                    .unwrap(),
-            }
+        .register_uri_scheme_protocol("model", |ctx, request| {
+            protocol::handle(ctx.app_handle(), &request)
         })
         .manage(Mutex::new(PetWindowState { initialized: false }))
+        .manage(fixups::Registry::builtin())
+        .manage(fixups::FixupLog::default())
+        .manage(cache::AssetCache::default())
+        .manage(import::InFlight::default())
+        .manage(BatchImport::default())
         .invoke_handler(tauri::generate_handler![
             load_model,
             get_config,
             get_model_preview,
+            get_model_variants,
+            set_model_variant,
+            change_cos,
             set_model_preview,
             apply_model,
             add_model,
             add_models_from_dir,
+            cancel_import,
+            inspect_lpk,
+            find_model_candidates,
+            set_lpk_stm_config,
+            export_lpk,
             remove_model,
             set_setting,
+            set_model_setting,
             get_setting,
             update_input_region,
             open_settings,
@@ -162,7 +69,15 @@ pub fn run() {
             set_model_motions,
             get_model_names,
             get_custom_motions,
+            get_model_intimacy,
+            set_model_intimacy,
             trigger_motion,
+            get_fixups,
+            set_fixup_enabled,
+            get_fixup_log,
+            get_asset_cache_stats,
+            validate_model,
+            repair_model,
             js_log
         ]);
 
@@ -175,9 +90,11 @@ pub fn run() {
     builder
         .setup(|app| {
             let handle = app.handle().clone();
+            config::init(&handle)?;
             tray::setup_tray(&handle)?;
+            import::clean_staging(&models_dir(&handle));
 
-            let cfg = config::load(&handle);
+            let cfg = config::load(&handle)?;
             eprintln!("[rive2d] current_model = {:?}", cfg.current_model);
             eprintln!("[rive2d] models = {:?}", cfg.models);
             if let Some(ref model_path) = cfg.current_model {
@@ -192,7 +109,7 @@ pub fn run() {
                 }
 
                 // Emit load-model event after a short delay for webview to initialize
-                let path = model_path.clone();
+                let path = config::active_variant_path(&handle, model_path)?;
                 let h = handle.clone();
                 std::thread::spawn(move || {
                     std::thread::sleep(std::time::Duration::from_millis(500));
@@ -246,52 +163,441 @@ pub fn create_config_window(app: &tauri::AppHandle) {
 }
 
 #[tauri::command]
-fn get_config(app: tauri::AppHandle) -> config::AppConfig {
+fn get_config(app: tauri::AppHandle) -> Result<config::AppConfig, String> {
     config::load(&app)
 }
 
 #[tauri::command]
-async fn add_model(app: tauri::AppHandle, path: String) -> Result<(), String> {
+async fn add_model(
+    app: tauri::AppHandle,
+    path: String,
+    on_duplicate: Option<import::OnDuplicate>,
+) -> Result<(), String> {
     let p = std::path::Path::new(&path);
     if !p.exists() {
         return Err("File not found".to_string());
     }
 
-    let hash = file_md5(p)?;
-    if config::has_hash(&app, &hash) {
-        return Err("Model already imported".to_string());
+    let source = if p.is_dir() {
+        let model =
+            lpk::find_model_json(p).ok_or("No .model3.json or .model.json found in folder")?;
+        import::ImportSource::Model(model.into())
+    } else if import::is_archive(p) {
+        import::ImportSource::Archive(p.to_path_buf())
+    } else if p.extension().and_then(|e| e.to_str()) == Some("json") {
+        import::ImportSource::Model(p.to_path_buf())
+    } else {
+        return Err("Unsupported format. Use .lpk, .zip or .model3.json".to_string());
+    };
+
+    let hash = import::file_md5(source.path())?;
+    let on_duplicate = on_duplicate.unwrap_or_default();
+    let in_flight = app.state::<import::InFlight>();
+    let _claim = in_flight
+        .claim(&hash)
+        .ok_or("Model is already being imported")?;
+    if let Some(existing) = config::find_by_source_hash(&app, &hash)? {
+        match (on_duplicate, &source) {
+            (import::OnDuplicate::Ask, _) => {
+                return Err(format!("{}: {}", import::DUPLICATE_ERROR, existing));
+            }
+            // The registered model already is this very file
+            (import::OnDuplicate::Replace, _) => return Ok(()),
+            // An archive can be installed a second time; a model registered
+            // in place is only registered once
+            (import::OnDuplicate::KeepBoth, import::ImportSource::Archive(_)) => {}
+            (import::OnDuplicate::KeepBoth, import::ImportSource::Model(_)) => {
+                return Err("Model already imported".to_string());
+            }
+        }
+    }
+    import_source(&app, &in_flight, &source, &hash, on_duplicate)
+}
+
+/// Install or register `source`, whose file hash is `hash`. Models with
+/// the same assets as a registered one are handled per `on_duplicate`.
+fn import_source(
+    app: &tauri::AppHandle,
+    in_flight: &import::InFlight,
+    source: &import::ImportSource,
+    hash: &str,
+    on_duplicate: import::OnDuplicate,
+) -> Result<(), String> {
+    match source {
+        import::ImportSource::Archive(p) => {
+            import_lpk(app, in_flight, &p.to_string_lossy(), hash, on_duplicate).map(|_| ())
+        }
+        import::ImportSource::Model(p) => {
+            descriptor::ModelDescriptor::load(p)
+                .map_err(|e| format!("Invalid model descriptor: {}", e))?;
+            let path = p.to_string_lossy();
+            let asset_hash = import::asset_hash(p).ok();
+            let (_claim, replaced) =
+                check_duplicate(app, in_flight, asset_hash.as_deref(), on_duplicate)?;
+            config::add_model(app, &path, Some(hash), asset_hash.as_deref())?;
+            if let Some(old) = replaced {
+                replace_model(app, &old, &path)?;
+            }
+            Ok(())
+        }
     }
+}
 
-    let model_path = match p.extension().and_then(|e| e.to_str()) {
-        Some("lpk") => extract_lpk(&app, &path)?,
-        Some("json") => path,
-        _ => return Err("Unsupported format. Use .lpk or .model3.json".to_string()),
+/// Look for a registered model with the content fingerprint `asset_hash`.
+/// Returns a claim on the fingerprint to hold until the new model is
+/// registered, and the model to replace once it is.
+fn check_duplicate<'a>(
+    app: &tauri::AppHandle,
+    in_flight: &'a import::InFlight,
+    asset_hash: Option<&str>,
+    on_duplicate: import::OnDuplicate,
+) -> Result<(Option<import::InFlightGuard<'a>>, Option<String>), String> {
+    let Some(asset_hash) = asset_hash.filter(|_| on_duplicate != import::OnDuplicate::KeepBoth)
+    else {
+        return Ok((None, None));
     };
+    let claim = in_flight
+        .claim(asset_hash)
+        .ok_or("Model is already being imported")?;
+
+    backfill_asset_hashes(app)?;
+    let existing = config::find_by_asset_hash(app, asset_hash)?;
+    match (existing, on_duplicate) {
+        (Some(existing), import::OnDuplicate::Ask) => {
+            Err(format!("{}: {}", import::DUPLICATE_ERROR, existing))
+        }
+        (existing, _) => Ok((Some(claim), existing)),
+    }
+}
 
-    config::add_model(&app, &model_path, Some(&hash));
+/// Fingerprint models registered before asset hashes existed. Models that
+/// can't be read get an empty hash so they are not retried every import.
+fn backfill_asset_hashes(app: &tauri::AppHandle) -> Result<(), String> {
+    for path in config::models_without_asset_hash(app)? {
+        let hash = import::asset_hash(std::path::Path::new(&path)).unwrap_or_default();
+        config::set_asset_hash(app, &path, &hash)?;
+    }
     Ok(())
 }
 
-fn file_md5(path: &std::path::Path) -> Result<String, String> {
-    let data = std::fs::read(path).map_err(|e| e.to_string())?;
-    Ok(format!("{:x}", md5::compute(&data)))
+/// Swap the library entry `old` for the freshly registered `new`, keeping
+/// it loaded if it was.
+fn replace_model(app: &tauri::AppHandle, old: &str, new: &str) -> Result<(), String> {
+    if old == new {
+        return Ok(());
+    }
+    // Files go only once the library no longer points at them
+    let was_current = config::replace_model(app, old, new, repaired_prefix(old).as_deref())?;
+    remove_install_dir(app, old);
+    app.state::<cache::AssetCache>().clear();
+    if was_current {
+        app.emit("load-model", config::active_variant_path(app, new)?)
+            .ok();
+    }
+    Ok(())
 }
 
-fn extract_lpk(app: &tauri::AppHandle, lpk_path: &str) -> Result<String, String> {
-    let lpk = std::path::Path::new(lpk_path);
-    let stem = lpk
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .ok_or("Invalid file name")?;
+/// Delete the installed package holding the model at `path` once no
+/// library entry lives in it anymore. Models registered in place are
+/// the user's own files and are left alone.
+fn remove_install_dir(app: &tauri::AppHandle, path: &str) {
+    let models = models_dir(app);
+    let dir = costume::package_root(&models, std::path::Path::new(path));
+    if !dir.starts_with(&models) {
+        return;
+    }
+    let result = config::has_model_in(app, &dir).and_then(|in_use| {
+        if in_use {
+            return Ok(());
+        }
+        std::fs::remove_dir_all(&dir).map_err(|e| e.to_string())
+    });
+    // The replacement is registered either way; a leftover only costs space
+    if let Err(e) = result {
+        eprintln!("[rive2d] Failed to remove {}: {}", dir.display(), e);
+    }
+}
+
+/// Drop the library entry `path` and everything stored for it. Returns
+/// whether it was the current model.
+fn forget_model(app: &tauri::AppHandle, path: &str) -> Result<bool, String> {
+    let is_current = config::get_setting(app, "current_model")?.as_deref() == Some(path);
+
+    config::remove_model(app, path)?;
+    if let Some(prefix) = repaired_prefix(path) {
+        let repaired = config::get_settings_with_prefix(app, &prefix)?;
+        let keys: Vec<&str> = repaired.keys().map(String::as_str).collect();
+        config::delete_settings(app, &keys)?;
+    }
+    // A re-import at the same path must not be served the old bodies
+    app.state::<cache::AssetCache>().clear();
+    Ok(is_current)
+}
+
+/// Prefix of the `repaired:` entries of files next to the model at `path`,
+/// which have to go with it: a re-import at the same path brings back
+/// unrepaired files.
+fn repaired_prefix(path: &str) -> Option<String> {
+    let dir = std::path::Path::new(path).parent()?;
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    Some(format!(
+        "{}{}{}",
+        repair::SETTING_PREFIX,
+        dir.to_string_lossy(),
+        std::path::MAIN_SEPARATOR
+    ))
+}
+
+/// Model descriptors in the folder `path`, best first, so the importer can
+/// let the user pick one when there are several.
+#[tauri::command]
+fn find_model_candidates(path: String) -> Result<Vec<lpk::ModelCandidate>, String> {
+    let dir = std::path::Path::new(&path);
+    if !dir.is_dir() {
+        return Err("Not a directory".to_string());
+    }
+    Ok(lpk::find_model_candidates(dir))
+}
 
-    let models_dir = app
-        .path()
+/// Describe an LPK without importing it, for previews in the import dialog.
+#[tauri::command]
+async fn inspect_lpk(app: tauri::AppHandle, path: String) -> Result<lpk::LpkInfo, String> {
+    // Overrides are keyed by the archive's hash; only STM archives use them,
+    // so don't hash the others
+    let stm = if lpk::is_stm_archive(&path)? {
+        stm_sources(&app, &import::file_md5(std::path::Path::new(&path))?)?
+    } else {
+        lpk::StmConfigSources::default()
+    };
+    lpk::inspect_lpk(&path, &stm)
+}
+
+/// Config key prefix of per-archive STM config overrides
+/// (`stm_config:<lpk md5>` = path to a config.json).
+const STM_CONFIG_PREFIX: &str = "stm_config:";
+
+/// Use `config` to decrypt the STM archive at `path` from now on, or go back
+/// to looking it up automatically if `None`.
+#[tauri::command]
+async fn set_lpk_stm_config(
+    app: tauri::AppHandle,
+    path: String,
+    config: Option<String>,
+) -> Result<(), String> {
+    let key = format!(
+        "{}{}",
+        STM_CONFIG_PREFIX,
+        import::file_md5(std::path::Path::new(&path))?
+    );
+    match config {
+        Some(config) => {
+            if !std::path::Path::new(&config).is_file() {
+                return Err("Config file not found".to_string());
+            }
+            config::set_setting(&app, &key, &config)
+        }
+        None => config::delete_settings(&app, &[&key]),
+    }
+}
+
+/// STM config locations for the archive with `hash`: its override, then
+/// the `stm_config_path` setting.
+fn stm_sources(app: &tauri::AppHandle, hash: &str) -> Result<lpk::StmConfigSources, String> {
+    Ok(lpk::StmConfigSources {
+        override_path: config::get_setting(app, &format!("{}{}", STM_CONFIG_PREFIX, hash))?
+            .map(Into::into),
+        user_path: config::settings(app)?.stm_config_path.map(Into::into),
+    })
+}
+
+/// Subdirectory of the models dir holding copies of custom previews.
+const PREVIEWS_DIR: &str = "previews";
+
+/// Directory holding extracted models, one subdirectory per installed
+/// archive, named after its hash.
+fn models_dir(app: &tauri::AppHandle) -> std::path::PathBuf {
+    app.path()
         .app_data_dir()
         .expect("Failed to get app data dir")
         .join("models")
-        .join(stem);
+}
+
+/// Extract an LPK into the models dir and register it in one step. Each
+/// model in it becomes a library entry, its first costume the entry and
+/// all of them its variants.
+fn import_lpk(
+    app: &tauri::AppHandle,
+    in_flight: &import::InFlight,
+    lpk_path: &str,
+    hash: &str,
+    on_duplicate: import::OnDuplicate,
+) -> Result<Vec<Vec<lpk::Costume>>, String> {
+    // A second copy of an installed archive goes next to the first
+    let models_dir = models_dir(app);
+    let mut dir_name = hash.to_string();
+    let mut n = 2;
+    while on_duplicate == import::OnDuplicate::KeepBoth
+        && config::has_model_in(app, &models_dir.join(&dir_name))?
+    {
+        dir_name = format!("{}-{}", hash, n);
+        n += 1;
+    }
+    let package_dir = models_dir.join(&dir_name);
+
+    import::install_lpk(
+        &models_dir,
+        lpk_path,
+        &dir_name,
+        &import_limits(app)?,
+        &stm_sources(app, hash)?,
+        |dir| config::has_model_in(app, dir),
+        |models| {
+            // Settle duplicates of every model before registering any,
+            // holding the claims until all are registered
+            let mut claims = Vec::new();
+            let mut found = Vec::new();
+            for costumes in models {
+                let asset_hash = import::asset_hash(std::path::Path::new(&costumes[0].path)).ok();
+                let (claim, replaced) =
+                    check_duplicate(app, in_flight, asset_hash.as_deref(), on_duplicate)?;
+                claims.extend(claim);
+                found.push((asset_hash, replaced));
+            }
+
+            let mut registered = Vec::new();
+            let result =
+                register_lpk_models(app, hash, &package_dir, models, &found, &mut registered);
+            // install_lpk deletes the files if this fails, so the rows must go too
+            if result.is_err() {
+                for path in registered {
+                    config::remove_model(app, path).ok();
+                }
+            }
+            result
+        },
+    )
+}
+
+/// Register the `models` installed from the archive with `hash` into
+/// `package_dir`, with the asset hash and the model to replace `found` for
+/// each. Paths are added to `registered` as they are.
+fn register_lpk_models<'a>(
+    app: &tauri::AppHandle,
+    hash: &str,
+    package_dir: &std::path::Path,
+    models: &'a [Vec<lpk::Costume>],
+    found: &[(Option<String>, Option<String>)],
+    registered: &mut Vec<&'a str>,
+) -> Result<(), String> {
+    for (costumes, (asset_hash, _)) in models.iter().zip(found) {
+        let variants: Vec<_> = costumes
+            .iter()
+            .map(|c| config::ModelVariant {
+                path: c.path.clone(),
+                name: c.name.clone(),
+                preview: c.avatar.clone(),
+            })
+            .collect();
+        config::add_model_with_variants(
+            app,
+            &costumes[0].path,
+            Some(hash),
+            asset_hash.as_deref(),
+            &variants,
+        )?;
+        registered.push(&costumes[0].path);
+    }
+    // Exported packages describe their default model
+    apply_pack_metadata(app, package_dir, &models[0][0].path)?;
+    for (costumes, (_, replaced)) in models.iter().zip(found) {
+        if let Some(old) = replaced {
+            replace_model(app, old, &costumes[0].path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Restore the settings a package exported by `export_lpk` carries.
+fn apply_pack_metadata(
+    app: &tauri::AppHandle,
+    package_dir: &std::path::Path,
+    model_path: &str,
+) -> Result<(), String> {
+    let Ok(data) = std::fs::read(package_dir.join(pack::METADATA_NAME)) else {
+        return Ok(());
+    };
+    let Ok(metadata) = serde_json::from_slice::<pack::PackMetadata>(&data) else {
+        return Ok(());
+    };
+    // Preview paths become servable, so they must stay inside the package
+    let preview = metadata.preview.and_then(|p| {
+        let root = package_dir.canonicalize().ok()?;
+        let path = package_dir.join(p).canonicalize().ok()?;
+        (path.starts_with(&root) && path.is_file()).then_some(path)
+    });
+    config::update_model_meta(app, model_path, |meta| {
+        if let Some(name) = metadata.name.filter(|n| !n.is_empty()) {
+            meta.name = Some(name);
+        }
+        if let Some(motions) = metadata.motions {
+            meta.motions = Some(motions.to_string());
+        }
+        if let Some(preview) = preview {
+            meta.preview = Some(preview.to_string_lossy().into_owned());
+        }
+    })
+}
 
-    lpk::extract_lpk(&models_dir, lpk_path)
+/// Export the library entry at `path` with its costumes, custom name,
+/// motion mappings and preview as an LPK at `out`.
+#[tauri::command]
+async fn export_lpk(
+    app: tauri::AppHandle,
+    path: String,
+    out: String,
+    format: pack::PackFormat,
+) -> Result<(), String> {
+    let mut costumes: Vec<_> = config::model_variants(&app, &path)?
+        .into_iter()
+        .map(|v| pack::PackCostume {
+            path: v.path.into(),
+            name: v.name,
+        })
+        .collect();
+    if costumes.is_empty() {
+        costumes.push(pack::PackCostume {
+            path: path.clone().into(),
+            name: None,
+        });
+    }
+    let meta = config::model_meta(&app, &path)?;
+    let metadata = pack::PackMetadata {
+        name: meta.name,
+        motions: meta.motions.and_then(|m| serde_json::from_str(&m).ok()),
+        preview: None,
+    };
+    let preview = get_model_preview(app.clone(), path)?;
+    pack::pack_lpk(
+        std::path::Path::new(&out),
+        &costumes,
+        format,
+        &metadata,
+        preview.as_deref().map(std::path::Path::new),
+    )
+}
+
+/// Archive limits for imports, overridable via the `import_max_total_size`,
+/// `import_max_entries` and `import_max_ratio` settings.
+fn import_limits(app: &tauri::AppHandle) -> Result<lpk::ExtractLimits, String> {
+    let defaults = lpk::ExtractLimits::default();
+    let settings = config::settings(app)?;
+    Ok(lpk::ExtractLimits {
+        max_total_size: settings
+            .import_max_total_size
+            .unwrap_or(defaults.max_total_size),
+        max_entries: settings.import_max_entries.unwrap_or(defaults.max_entries),
+        max_ratio: settings.import_max_ratio.unwrap_or(defaults.max_ratio),
+    })
 }
 
 #[derive(serde::Serialize)]
@@ -299,111 +605,199 @@ struct ImportResult {
     imported: u32,
     skipped: u32,
     errors: Vec<String>,
+    /// Whether `cancel_import` stopped the import early
+    cancelled: bool,
 }
 
 #[derive(serde::Serialize, Clone)]
 struct ImportProgress {
+    /// Files finished so far
     current: u32,
     total: u32,
     name: String,
+    /// "importing", "imported", "skipped" or "failed"
+    status: &'static str,
+    error: Option<String>,
+    /// Size of the finished files, in bytes
+    bytes_processed: u64,
+    bytes_total: u64,
+}
+
+/// The running folder import. Only one runs at a time, so `cancel_import`
+/// cannot stop someone else's.
+#[derive(Default)]
+struct BatchImport {
+    running: AtomicBool,
+    /// Set by `cancel_import` to stop the import between files
+    cancel: AtomicBool,
+}
+
+impl BatchImport {
+    /// Mark a folder import as started, or `None` if one is running. It
+    /// ends when the guard is dropped.
+    fn start(&self) -> Option<BatchGuard<'_>> {
+        if self.running.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        self.cancel.store(false, Ordering::Relaxed);
+        Some(BatchGuard(self))
+    }
+}
+
+struct BatchGuard<'a>(&'a BatchImport);
+
+impl Drop for BatchGuard<'_> {
+    fn drop(&mut self) {
+        self.0.running.store(false, Ordering::Release);
+    }
+}
+
+/// Counters of a running folder import.
+#[derive(Default)]
+struct BatchState {
+    imported: u32,
+    skipped: u32,
+    errors: Vec<String>,
+    finished: u32,
+    bytes_processed: u64,
 }
 
 #[tauri::command]
-async fn add_models_from_dir(app: tauri::AppHandle, path: String) -> Result<ImportResult, String> {
+async fn add_models_from_dir(
+    app: tauri::AppHandle,
+    path: String,
+    on_duplicate: Option<import::OnDuplicate>,
+) -> Result<ImportResult, String> {
     let dir = std::path::Path::new(&path);
     if !dir.is_dir() {
         return Err("Not a directory".to_string());
     }
+    let batch = app.state::<BatchImport>();
+    let _running = batch
+        .start()
+        .ok_or("Another folder import is already running")?;
 
-    // Phase 1: collect all .lpk paths
-    let mut lpk_files = Vec::new();
-    collect_lpk_files(dir, 0, 5, &mut lpk_files);
+    // Phase 1: collect archives and unpacked models
+    let sources = import::collect_sources(dir, 5);
 
-    if lpk_files.is_empty() {
-        return Err("No .lpk files found".to_string());
+    if sources.is_empty() {
+        return Err("No .lpk, .zip or model files found".to_string());
     }
 
-    let total = lpk_files.len() as u32;
-    let mut imported = 0u32;
-    let mut skipped = 0u32;
-    let mut errors = Vec::new();
+    let total = sources.len() as u32;
+    let sizes: Vec<u64> = sources
+        .iter()
+        .map(|s| std::fs::metadata(s.path()).map(|m| m.len()).unwrap_or(0))
+        .collect();
+    let bytes_total = sizes.iter().sum();
+    let cancel = &batch.cancel;
+    let in_flight = app.state::<import::InFlight>();
+    let state = Mutex::new(BatchState::default());
+
+    let progress = |state: &BatchState, name: &str, status, error| ImportProgress {
+        current: state.finished,
+        total,
+        name: name.to_string(),
+        status,
+        error,
+        bytes_processed: state.bytes_processed,
+        bytes_total,
+    };
 
-    // Phase 2: process with progress events
-    for (i, lpk_path) in lpk_files.iter().enumerate() {
-        let name = lpk_path
+    // Phase 2: process on a bounded pool with progress events
+    let workers = import_workers(&app)?;
+    import::run_pool(&sources, workers, cancel, |i, source| {
+        let name = source
+            .path()
             .file_name()
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_default();
+        {
+            let state = state.lock().unwrap();
+            app.emit(
+                "import-progress",
+                progress(&state, &name, "importing", None),
+            )
+            .ok();
+        }
 
-        app.emit(
-            "import-progress",
-            ImportProgress {
-                current: i as u32 + 1,
-                total,
-                name: name.clone(),
-            },
-        )
-        .ok();
+        let result = import_batch_file(&app, &in_flight, source, on_duplicate.unwrap_or_default());
 
-        let path_str = lpk_path.to_string_lossy().to_string();
-        let hash = match file_md5(lpk_path) {
-            Ok(h) => h,
-            Err(_) => continue,
-        };
-        if config::has_hash(&app, &hash) {
-            skipped += 1;
-            continue;
-        }
-        match extract_lpk(&app, &path_str) {
-            Ok(model_path) => {
-                config::add_model(&app, &model_path, Some(&hash));
-                imported += 1;
+        // Emit under the lock so counters arrive in order
+        let mut state = state.lock().unwrap();
+        state.finished += 1;
+        state.bytes_processed += sizes[i];
+        let (status, error) = match result {
+            Ok(true) => {
+                state.imported += 1;
+                ("imported", None)
+            }
+            Ok(false) => {
+                state.skipped += 1;
+                ("skipped", None)
             }
             Err(e) => {
-                errors.push(format!("{}: {}", name, e));
+                state.errors.push(format!("{}: {}", name, e));
+                ("failed", Some(e))
             }
-        }
-    }
+        };
+        app.emit("import-progress", progress(&state, &name, status, error))
+            .ok();
+    });
 
+    let state = state.into_inner().unwrap();
     Ok(ImportResult {
-        imported,
-        skipped,
-        errors,
+        imported: state.imported,
+        skipped: state.skipped,
+        errors: state.errors,
+        cancelled: cancel.load(Ordering::Relaxed),
     })
 }
 
-fn collect_lpk_files(
-    dir: &std::path::Path,
-    depth: u32,
-    max_depth: u32,
-    out: &mut Vec<std::path::PathBuf>,
-) {
-    if depth > max_depth {
-        return;
-    }
-    let Ok(entries) = std::fs::read_dir(dir) else {
-        return;
+/// Import one file of a folder import. `Ok(false)` means it was skipped as
+/// already imported (or being imported by another worker), or as a
+/// duplicate when `on_duplicate` is `Ask`.
+fn import_batch_file(
+    app: &tauri::AppHandle,
+    in_flight: &import::InFlight,
+    source: &import::ImportSource,
+    on_duplicate: import::OnDuplicate,
+) -> Result<bool, String> {
+    let hash = import::file_md5(source.path())?;
+    let Some(_claim) = in_flight.claim(&hash) else {
+        return Ok(false);
     };
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.is_dir() {
-            collect_lpk_files(&path, depth + 1, max_depth, out);
-        } else if path.extension().and_then(|e| e.to_str()) == Some("lpk") {
-            out.push(path);
-        }
+    if config::has_hash(app, &hash)? {
+        return Ok(false);
+    }
+    match import_source(app, in_flight, source, &hash, on_duplicate) {
+        Ok(()) => Ok(true),
+        Err(e) if e.starts_with(import::DUPLICATE_ERROR) => Ok(false),
+        Err(e) => Err(e),
     }
 }
 
-#[tauri::command]
-fn remove_model(app: tauri::AppHandle, path: String) -> Result<(), String> {
-    // Check if this is the currently loaded model before removing
-    let is_current = config::get_setting(&app, "current_model")
-        .map(|v| v == path)
-        .unwrap_or(false);
+/// Worker threads for folder imports: the `import_workers` setting, or up
+/// to four depending on the CPU count.
+fn import_workers(app: &tauri::AppHandle) -> Result<usize, String> {
+    Ok(config::settings(app)?.import_workers.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get().min(4))
+            .unwrap_or(1)
+    }))
+}
 
-    config::remove_model(&app, &path);
+/// Stop the running folder import once the files in progress are done.
+#[tauri::command]
+fn cancel_import(app: tauri::AppHandle) {
+    app.state::<BatchImport>()
+        .cancel
+        .store(true, Ordering::Relaxed);
+}
 
-    if is_current {
+#[tauri::command]
+fn remove_model(app: tauri::AppHandle, path: String) -> Result<(), String> {
+    if forget_model(&app, &path)? {
         app.emit("unload-model", ()).ok();
     }
 
@@ -412,10 +806,9 @@ fn remove_model(app: tauri::AppHandle, path: String) -> Result<(), String> {
 
 #[tauri::command]
 async fn apply_model(app: tauri::AppHandle, path: String) -> Result<(), String> {
-    config::set_model(&app, &path);
-
-    // Clear old model's position/scale so new model starts centered
-    config::delete_settings(&app, &["model_x", "model_y", "model_scale"]);
+    // Position and scale are per model, so the new model comes back where
+    // it was left, or centered
+    config::set_model(&app, &path)?;
 
     // Initialize layer shell if not yet done
     let needs_init = {
@@ -441,7 +834,7 @@ async fn apply_model(app: tauri::AppHandle, path: String) -> Result<(), String>
     }
 
     // Emit model load event after a short delay for webview to initialize
-    let path_clone = path.clone();
+    let path_clone = config::active_variant_path(&app, &path)?;
     let handle = app.clone();
     std::thread::spawn(move || {
         std::thread::sleep(std::time::Duration::from_millis(500));
@@ -457,48 +850,100 @@ async fn apply_model(app: tauri::AppHandle, path: String) -> Result<(), String>
 }
 
 #[tauri::command]
-fn set_setting(app: tauri::AppHandle, key: String, value: String) {
-    config::set_setting(&app, &key, &value);
-    app.emit("setting-changed", (&key, &value)).ok();
+fn set_setting(app: tauri::AppHandle, key: String, value: String) -> Result<(), String> {
+    settings::validate(&key, &value)?;
+    config::set_setting(&app, &key, &value)?;
+    emit_setting(&app, &key)
+}
+
+/// Override the setting `key` for the library entry at `path`, or reset it
+/// to the global value if `value` is `None`.
+#[tauri::command]
+fn set_model_setting(
+    app: tauri::AppHandle,
+    path: String,
+    key: String,
+    value: Option<String>,
+) -> Result<(), String> {
+    match &value {
+        Some(value) => settings::validate_per_model(&key, value)?,
+        None if !settings::PER_MODEL.contains(&key.as_str()) => {
+            return Err(format!("Not a per-model setting: {}", key));
+        }
+        None => {}
+    }
+    config::set_model_setting(&app, &path, &key, value.as_deref())?;
+    if config::get_setting(&app, "current_model")?.as_deref() == Some(path.as_str()) {
+        emit_setting(&app, &key)?;
+    }
+    Ok(())
+}
+
+/// Tell the windows the value `key` now has for the current model, which
+/// may be its override rather than the global value just changed.
+fn emit_setting(app: &tauri::AppHandle, key: &str) -> Result<(), String> {
+    if let Some(value) = config::effective_setting(app, key)? {
+        app.emit("setting-changed", (key, value)).ok();
+    }
+    Ok(())
+}
+
+/// Forget the saved position and scale of the current model, and the
+/// global fallbacks.
+pub fn reset_position(app: &tauri::AppHandle) -> Result<(), String> {
+    let keys = ["model_x", "model_y", "model_scale"];
+    if let Some(model) = config::get_setting(app, "current_model")? {
+        for key in keys {
+            config::set_model_setting(app, &model, key, None)?;
+        }
+    }
+    config::delete_settings(app, &keys)
 }
 
+/// Stored global value of the setting `key`. Only settings are readable,
+/// not the rest of the config table.
 #[tauri::command]
-fn get_setting(app: tauri::AppHandle, key: String) -> Option<String> {
+fn get_setting(app: tauri::AppHandle, key: String) -> Result<Option<String>, String> {
+    if !settings::KEYS.contains(&key.as_str()) {
+        return Err(format!("Unknown setting: {}", key));
+    }
     config::get_setting(&app, &key)
 }
 
 /// Given a model JSON path, return the absolute path to a preview image.
-/// Checks for a user-uploaded preview first, then falls back to the model's first texture.
+/// Checks for a user-uploaded preview first, then the selected variant's
+/// avatar, then falls back to the first texture of the selected variant.
 #[tauri::command]
-fn get_model_preview(app: tauri::AppHandle, path: String) -> Option<String> {
-    // Check for custom preview in DB
-    let custom = config::get_setting(&app, &format!("preview:{}", path));
+fn get_model_preview(app: tauri::AppHandle, path: String) -> Result<Option<String>, String> {
+    // Check for custom preview in DB. Previews set before they were copied
+    // into the models dir can't be served anymore.
+    let custom = config::model_meta(&app, &path)?.preview;
     if let Some(ref p) = custom {
-        if std::path::Path::new(p).exists() {
-            return custom;
+        let p = std::path::Path::new(p);
+        if p.exists() && p.starts_with(models_dir(&app)) {
+            return Ok(custom);
         }
     }
 
+    let variant = config::active_variant_path(&app, &path)?;
+    let avatar = config::model_variants(&app, &path)?
+        .into_iter()
+        .find(|v| v.path == variant)
+        .and_then(|v| v.preview)
+        .filter(|p| std::path::Path::new(p).exists());
+    if avatar.is_some() {
+        return Ok(avatar);
+    }
+
     // Fall back to first texture from model JSON
-    let model_path = std::path::Path::new(&path);
+    Ok(texture_preview(std::path::Path::new(&variant)))
+}
+
+fn texture_preview(model_path: &std::path::Path) -> Option<String> {
     let dir = model_path.parent()?;
-    let json_str = std::fs::read_to_string(model_path).ok()?;
-    let json: serde_json::Value = serde_json::from_str(&json_str).ok()?;
-
-    let texture = json
-        .get("textures")
-        .and_then(|v| v.as_array())
-        .and_then(|a| a.first())
-        .and_then(|v| v.as_str())
-        .or_else(|| {
-            json.get("FileReferences")
-                .and_then(|fr| fr.get("Textures"))
-                .and_then(|v| v.as_array())
-                .and_then(|a| a.first())
-                .and_then(|v| v.as_str())
-        })?;
-
-    let abs = dir.join(texture);
+    let desc = descriptor::ModelDescriptor::load(model_path).ok()?;
+
+    let abs = dir.join(desc.first_texture()?);
     if abs.exists() {
         Some(abs.to_string_lossy().into_owned())
     } else {
@@ -506,15 +951,98 @@ fn get_model_preview(app: tauri::AppHandle, path: String) -> Option<String> {
     }
 }
 
+#[derive(serde::Serialize)]
+struct VariantInfo {
+    #[serde(flatten)]
+    variant: config::ModelVariant,
+    active: bool,
+}
+
+/// Costumes of the library entry at `path`; empty if it has none.
 #[tauri::command]
-fn set_model_preview(app: tauri::AppHandle, model_path: String, image_path: String) -> Result<(), String> {
-    if !std::path::Path::new(&image_path).exists() {
-        return Err("Image file not found".to_string());
+fn get_model_variants(app: tauri::AppHandle, path: String) -> Result<Vec<VariantInfo>, String> {
+    let active = config::active_variant_path(&app, &path)?;
+    Ok(config::model_variants(&app, &path)?
+        .into_iter()
+        .map(|variant| VariantInfo {
+            active: variant.path == active,
+            variant,
+        })
+        .collect())
+}
+
+/// Select the costume of the library entry at `path`, reloading the pet
+/// window if the entry is the current model.
+#[tauri::command]
+fn set_model_variant(app: tauri::AppHandle, path: String, variant: String) -> Result<(), String> {
+    if !config::model_variants(&app, &path)?
+        .iter()
+        .any(|v| v.path == variant)
+    {
+        return Err("Unknown model variant".to_string());
+    }
+    config::update_model_meta(&app, &path, |meta| {
+        meta.active_variant = Some(variant.clone());
+    })?;
+
+    if config::get_setting(&app, "current_model")?.as_deref() == Some(path.as_str()) {
+        app.emit("load-model", &variant).ok();
     }
-    config::set_setting(&app, &format!("preview:{}", model_path), &image_path);
     Ok(())
 }
 
+/// Switch the current model to a sibling descriptor in its package, for the
+/// `change_cos` motion command. Unlike `apply_model`, position and scale are
+/// kept; the costume is remembered as the current model's variant.
+#[tauri::command]
+fn change_cos(app: tauri::AppHandle, target: String) -> Result<String, String> {
+    let model = config::get_setting(&app, "current_model")?.ok_or("No model loaded")?;
+    let current = config::active_variant_path(&app, &model)?;
+    let resolved = costume::resolve(
+        &models_dir(&app),
+        std::path::Path::new(&model),
+        std::path::Path::new(&current),
+        &target,
+    )?;
+
+    // Reuse the registered spelling of the path if the costume is known
+    let same = |p: &str| std::path::Path::new(p).canonicalize().ok().as_ref() == Some(&resolved);
+    let path = std::iter::once(model.clone())
+        .chain(
+            config::model_variants(&app, &model)?
+                .into_iter()
+                .map(|v| v.path),
+        )
+        .find(|p| same(p))
+        .unwrap_or_else(|| resolved.to_string_lossy().into_owned());
+    config::add_model_variant(&app, &model, &path)?;
+    config::update_model_meta(&app, &model, |meta| {
+        meta.active_variant = Some(path.clone());
+    })?;
+
+    app.emit("load-model", &path).ok();
+    Ok(path)
+}
+
+/// Use a copy of the image at `image_path` as the preview of the library
+/// entry at `model_path`, returning the copy's path.
+#[tauri::command]
+fn set_model_preview(
+    app: tauri::AppHandle,
+    model_path: String,
+    image_path: String,
+) -> Result<String, String> {
+    let stored = preview::store(
+        &models_dir(&app).join(PREVIEWS_DIR),
+        std::path::Path::new(&image_path),
+    )?;
+    let stored = stored.to_string_lossy().into_owned();
+    config::update_model_meta(&app, &model_path, |meta| {
+        meta.preview = Some(stored.clone())
+    })?;
+    Ok(stored)
+}
+
 #[tauri::command]
 fn load_model(path: String) -> Result<String, String> {
     let path = std::path::Path::new(&path);
@@ -575,122 +1103,76 @@ struct ModelInfo {
 
 #[tauri::command]
 fn get_model_info(app: tauri::AppHandle, path: String) -> Result<ModelInfo, String> {
-    let json_str = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    let json: serde_json::Value = serde_json::from_str(&json_str).map_err(|e| e.to_string())?;
-
-    let mut hit_areas = Vec::new();
-    let mut motion_groups = Vec::new();
-
-    // Cubism 3/4: HitAreas with {Name, Id, Motion}
-    if let Some(areas) = json.get("HitAreas").and_then(|v| v.as_array()) {
-        for area in areas {
-            let name = area
-                .get("Name")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-            let id = area
-                .get("Id")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-            let motion = area
-                .get("Motion")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string());
-            hit_areas.push(HitAreaInfo {
-                name,
-                id,
-                default_motion: motion,
-            });
-        }
-    }
-
-    // Cubism 2: hit_areas with {name, id}
-    if hit_areas.is_empty() {
-        if let Some(areas) = json.get("hit_areas").and_then(|v| v.as_array()) {
-            for area in areas {
-                let name = area
-                    .get("name")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                let id = area
-                    .get("id")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                hit_areas.push(HitAreaInfo {
-                    name,
-                    id,
-                    default_motion: None,
-                });
-            }
-        }
-    }
-
-    // Motion groups - Cubism 3/4
-    if let Some(motions) = json
-        .get("FileReferences")
-        .and_then(|fr| fr.get("Motions"))
-        .and_then(|v| v.as_object())
-    {
-        motion_groups = motions.keys().cloned().collect();
-    }
-    // Motion groups - Cubism 2
-    if motion_groups.is_empty() {
-        if let Some(motions) = json.get("motions").and_then(|v| v.as_object()) {
-            motion_groups = motions.keys().cloned().collect();
-        }
-    }
-    motion_groups.sort();
+    let desc = descriptor::ModelDescriptor::load(std::path::Path::new(&path))?;
+
+    let motion_groups = desc.motion_groups();
+    let hit_areas = desc
+        .hit_areas
+        .into_iter()
+        .map(|area| HitAreaInfo {
+            name: area.name,
+            id: area.id,
+            default_motion: area.motion,
+        })
+        .collect();
 
-    let custom_name = config::get_setting(&app, &format!("name:{}", path));
-    let custom_motions = config::get_setting(&app, &format!("motions:{}", path));
+    let meta = config::model_meta(&app, &path)?;
 
     Ok(ModelInfo {
         hit_areas,
         motion_groups,
-        custom_name,
-        custom_motions,
+        custom_name: meta.name,
+        custom_motions: meta.motions,
     })
 }
 
 #[tauri::command]
-fn set_model_name(app: tauri::AppHandle, path: String, name: String) {
-    let key = format!("name:{}", path);
-    if name.is_empty() {
-        config::delete_settings(&app, &[&key]);
-    } else {
-        config::set_setting(&app, &key, &name);
-    }
+fn set_model_name(app: tauri::AppHandle, path: String, name: String) -> Result<(), String> {
+    config::update_model_meta(&app, &path, |meta| {
+        meta.name = Some(name).filter(|n| !n.is_empty());
+    })
 }
 
 #[tauri::command]
-fn set_model_motions(app: tauri::AppHandle, path: String, mappings: String) {
-    let key = format!("motions:{}", path);
-    if mappings == "{}" || mappings.is_empty() {
-        config::delete_settings(&app, &[&key]);
-    } else {
-        config::set_setting(&app, &key, &mappings);
-    }
+fn set_model_motions(app: tauri::AppHandle, path: String, mappings: String) -> Result<(), String> {
+    config::update_model_meta(&app, &path, |meta| {
+        meta.motions = Some(mappings).filter(|m| m != "{}" && !m.is_empty());
+    })?;
     app.emit("motions-changed", &path).ok();
+    Ok(())
 }
 
 #[tauri::command]
-fn get_model_names(app: tauri::AppHandle, paths: Vec<String>) -> HashMap<String, String> {
+fn get_model_names(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Result<HashMap<String, String>, String> {
     let mut result = HashMap::new();
     for path in paths {
-        if let Some(name) = config::get_setting(&app, &format!("name:{}", path)) {
+        if let Some(name) = config::model_meta(&app, &path)?.name {
             result.insert(path, name);
         }
     }
-    result
+    Ok(result)
+}
+
+#[tauri::command]
+fn get_custom_motions(app: tauri::AppHandle, path: String) -> Result<Option<String>, String> {
+    Ok(config::model_meta(&app, &path)?.motions)
 }
 
+/// Saved intimacy of the library entry at `path`, if any.
 #[tauri::command]
-fn get_custom_motions(app: tauri::AppHandle, path: String) -> Option<String> {
-    config::get_setting(&app, &format!("motions:{}", path))
+fn get_model_intimacy(app: tauri::AppHandle, path: String) -> Result<Option<f64>, String> {
+    Ok(config::model_meta(&app, &path)?.intimacy)
+}
+
+#[tauri::command]
+fn set_model_intimacy(app: tauri::AppHandle, path: String, value: f64) -> Result<(), String> {
+    if !value.is_finite() {
+        return Err("Invalid intimacy value".to_string());
+    }
+    config::update_model_meta(&app, &path, |meta| meta.intimacy = Some(value))
 }
 
 #[tauri::command]
@@ -698,6 +1180,58 @@ fn trigger_motion(app: tauri::AppHandle, group: String, index: Option<u32>) {
     app.emit("trigger-motion", (group, index)).ok();
 }
 
+#[tauri::command]
+fn get_fixups(app: tauri::AppHandle) -> Result<Vec<fixups::FixupInfo>, String> {
+    let flags = config::get_settings_with_prefix(&app, fixups::SETTING_PREFIX)?;
+    Ok(app.state::<fixups::Registry>().list(&flags))
+}
+
+#[tauri::command]
+fn set_fixup_enabled(app: tauri::AppHandle, id: String, enabled: bool) -> Result<(), String> {
+    if !app.state::<fixups::Registry>().contains(&id) {
+        return Err(format!("Unknown fixup: {}", id));
+    }
+    let key = format!("{}{}", fixups::SETTING_PREFIX, id);
+    config::set_setting(&app, &key, if enabled { "true" } else { "false" })?;
+    // Cached bodies were patched with the old set of fixups
+    app.state::<cache::AssetCache>().clear();
+    Ok(())
+}
+
+/// Fixups that fired for a model's files since startup.
+#[tauri::command]
+fn get_fixup_log(app: tauri::AppHandle, path: String) -> Vec<fixups::LogEntry> {
+    app.state::<fixups::FixupLog>().get(&path)
+}
+
+#[tauri::command]
+fn get_asset_cache_stats(app: tauri::AppHandle) -> cache::CacheStats {
+    app.state::<cache::AssetCache>().stats()
+}
+
+#[tauri::command]
+fn validate_model(path: String) -> Result<validate::ValidationReport, String> {
+    validate::validate_model(std::path::Path::new(&path))
+}
+
+/// Write the enabled fixups to the model's files and stop patching the
+/// rewritten files with them in flight.
+#[tauri::command]
+fn repair_model(app: tauri::AppHandle, path: String) -> Result<repair::RepairReport, String> {
+    // Only rewrite files of the library, like `model://` only serves them
+    if !config::is_registered(&app, &path)? {
+        return Err(format!("Model not registered: {}", path));
+    }
+    let flags = config::get_settings_with_prefix(&app, fixups::SETTING_PREFIX)?;
+    let registry = app.state::<fixups::Registry>();
+    let report = repair::repair_model(std::path::Path::new(&path), &registry, &flags)?;
+    for (key, value) in repair::repaired_settings(&report, &registry, &flags) {
+        config::set_setting(&app, &key, &value)?;
+    }
+    app.state::<cache::AssetCache>().clear();
+    Ok(report)
+}
+
 #[tauri::command]
 fn js_log(level: String, msg: String) {
     eprintln!("[rive2d:js:{}] {}", level, msg);
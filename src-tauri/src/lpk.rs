@@ -1,32 +1,188 @@
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::io::Read;
-use std::path::Path;
+use crate::descriptor::{CubismVersion, ModelDescriptor};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
 /// Manifest file name inside LPK archives
-const MANIFEST_NAME: &str = "config.mlve";
+pub(crate) const MANIFEST_NAME: &str = "config.mlve";
 
-/// Extract an LPK file to `dest_dir`, returning the path to the .model3.json/.model.json.
+/// Bytes read ahead of each entry for file type detection
+const HEADER_LEN: u64 = 4096;
+
+/// Upper bound for entries that are read whole into memory
+const MAX_METADATA_LEN: u64 = 1024 * 1024;
+
+/// Limits checked against an archive before anything is extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractLimits {
+    /// Maximum sum of uncompressed entry sizes, in bytes
+    pub max_total_size: u64,
+    pub max_entries: usize,
+    /// Maximum uncompressed/compressed size ratio of a single entry
+    pub max_ratio: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_total_size: 4 * 1024 * 1024 * 1024,
+            max_entries: 20_000,
+            max_ratio: 200,
+        }
+    }
+}
+
+/// Entries smaller than this are exempt from the ratio check; tiny, highly
+/// repetitive JSON files compress very well without being a threat.
+const RATIO_MIN_SIZE: u64 = 1024 * 1024;
+
+/// A model descriptor extracted from an LPK.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Costume {
+    /// Path to the .model3.json/.model.json
+    pub path: String,
+    /// Costume name from the manifest
+    pub name: Option<String>,
+    /// Path to the character's preview image
+    pub avatar: Option<String>,
+}
+
+/// Extract an LPK file to `dest_dir`, returning its models, each as its
+/// costumes with the default first. A Live2DViewerEX package is one model
+/// with every costume of its manifest; a regular archive holds one model
+/// per distinct descriptor (see `distinct_models`), best ranked first.
 ///
 /// Handles both regular (unencrypted) LPK files and Live2DViewerEX-style
 /// encrypted LPK files (STM_1_0 / STD_1_0 / STD_2_0 formats).
-pub fn extract_lpk(dest_dir: &Path, lpk_path: &str) -> Result<String, String> {
-    // Clean destination directory to avoid stale files from previous extractions
+///
+/// `dest_dir` must not exist yet. The archive is checked against `limits`
+/// and for unsafe entry names up front; if it is rejected or extraction
+/// fails, `dest_dir` is removed. STM archives are decrypted with the first
+/// config found through `stm`.
+pub fn extract_lpk(
+    dest_dir: &Path,
+    lpk_path: &str,
+    limits: &ExtractLimits,
+    stm: &StmConfigSources,
+) -> Result<Vec<Vec<Costume>>, String> {
+    let file = std::fs::File::open(lpk_path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    check_archive(&mut archive, limits)?;
+
+    // Never extract over (and later clean up) an existing directory
     if dest_dir.exists() {
-        std::fs::remove_dir_all(dest_dir).map_err(|e| e.to_string())?;
+        return Err(format!(
+            "Destination already exists: {}",
+            dest_dir.display()
+        ));
     }
     std::fs::create_dir_all(dest_dir).map_err(|e| e.to_string())?;
 
-    let file = std::fs::File::open(lpk_path).map_err(|e| e.to_string())?;
-    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
-
     // Try to find the manifest (config.mlve or its MD5-hashed name)
     let manifest = read_manifest(&mut archive);
 
-    match manifest {
-        Some(manifest) => extract_encrypted_lpk(dest_dir, lpk_path, &mut archive, &manifest),
+    let result = match manifest {
+        Some(manifest) => extract_encrypted_lpk(dest_dir, lpk_path, &mut archive, &manifest, stm)
+            .map(|costumes| vec![costumes]),
         None => extract_regular_lpk(dest_dir, &mut archive),
+    };
+    if result.is_err() {
+        std::fs::remove_dir_all(dest_dir).ok();
+    }
+    result
+}
+
+// ---------------------------------------------------------------------------
+// Archive safety
+// ---------------------------------------------------------------------------
+
+/// Reject archives with unsafe entries or that exceed `limits`, using only
+/// the central directory (nothing is decompressed).
+fn check_archive(
+    archive: &mut zip::ZipArchive<std::fs::File>,
+    limits: &ExtractLimits,
+) -> Result<(), String> {
+    if archive.len() > limits.max_entries {
+        return Err(format!(
+            "Archive has {} entries, more than the limit of {}",
+            archive.len(),
+            limits.max_entries
+        ));
+    }
+
+    let mut total: u64 = 0;
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i).map_err(|e| e.to_string())?;
+        let name = entry.name();
+        if entry.is_symlink() {
+            return Err(format!("Archive entry {} is a symbolic link", name));
+        }
+        entry_path(name)?;
+
+        let size = entry.size();
+        total = total.saturating_add(size);
+        if total > limits.max_total_size {
+            return Err(format!(
+                "Archive expands to more than the limit of {} bytes",
+                limits.max_total_size
+            ));
+        }
+        let ratio = size / entry.compressed_size().max(1);
+        if size >= RATIO_MIN_SIZE && ratio > limits.max_ratio {
+            return Err(format!(
+                "Archive entry {} has a compression ratio of {}:1, more than the limit of {}:1",
+                name, ratio, limits.max_ratio
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Relative output path for an archive entry name. Absolute names, `..`
+/// components and drive prefixes are rejected so nothing can be written
+/// outside the extraction directory.
+fn entry_path(name: &str) -> Result<PathBuf, String> {
+    let unsafe_name = || format!("Archive entry has an unsafe name: {}", name);
+    let normalized = name.replace('\\', "/");
+    if normalized.starts_with('/') {
+        return Err(unsafe_name());
+    }
+    let mut path = PathBuf::new();
+    for part in normalized.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return Err(unsafe_name()),
+            _ if part.contains(':') || part.contains('\0') => return Err(unsafe_name()),
+            _ => path.push(part),
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err(unsafe_name());
+    }
+    Ok(path)
+}
+
+/// Stream an entry to `out_path`, failing if it produces more than the
+/// `declared` size checked by `check_archive`.
+fn write_entry(
+    out_path: &Path,
+    reader: impl Read,
+    declared: u64,
+    name: &str,
+) -> Result<(), String> {
+    if let Some(parent) = out_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
+    let out = std::fs::File::create(out_path).map_err(|e| e.to_string())?;
+    let mut out = std::io::BufWriter::new(out);
+    let written = std::io::copy(&mut reader.take(declared + 1), &mut out)
+        .and_then(|n| out.flush().map(|_| n))
+        .map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+    if written > declared {
+        return Err(format!("Archive entry {} is larger than declared", name));
+    }
+    Ok(())
 }
 
 // ---------------------------------------------------------------------------
@@ -36,11 +192,54 @@ pub fn extract_lpk(dest_dir: &Path, lpk_path: &str) -> Result<String, String> {
 fn extract_regular_lpk(
     dest_dir: &Path,
     archive: &mut zip::ZipArchive<std::fs::File>,
-) -> Result<String, String> {
-    std::fs::create_dir_all(dest_dir).map_err(|e| e.to_string())?;
-    archive.extract(dest_dir).map_err(|e| e.to_string())?;
-    find_model_json(dest_dir)
-        .ok_or_else(|| "No .model3.json or .model.json found in archive".to_string())
+) -> Result<Vec<Vec<Costume>>, String> {
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        let out_path = dest_dir.join(entry_path(entry.name())?);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&out_path).map_err(|e| e.to_string())?;
+            continue;
+        }
+        let (name, declared) = (entry.name().to_string(), entry.size());
+        write_entry(&out_path, entry, declared, &name)?;
+    }
+    let candidates = find_model_candidates(dest_dir);
+    if candidates.is_empty() {
+        return Err("No .model3.json or .model.json found in archive".to_string());
+    }
+    Ok(distinct_models(candidates)
+        .into_iter()
+        .map(|c| {
+            vec![Costume {
+                path: c.path,
+                name: None,
+                avatar: None,
+            }]
+        })
+        .collect())
+}
+
+/// The models among ranked `candidates`, best first: every valid
+/// descriptor, except Cubism 2 ones left next to a Cubism 3 descriptor.
+/// Falls back to the best candidate so an invalid one is reported as such.
+pub fn distinct_models(candidates: Vec<ModelCandidate>) -> Vec<ModelCandidate> {
+    let cubism3_dirs: HashSet<PathBuf> = candidates
+        .iter()
+        .filter(|c| c.valid && c.version == CubismVersion::Cubism3)
+        .filter_map(|c| Path::new(&c.path).parent().map(Path::to_path_buf))
+        .collect();
+    let (valid, rest): (Vec<_>, Vec<_>) = candidates.into_iter().partition(|c| {
+        c.valid
+            && (c.version == CubismVersion::Cubism3
+                || !Path::new(&c.path)
+                    .parent()
+                    .is_some_and(|d| cubism3_dirs.contains(d)))
+    });
+    if valid.is_empty() {
+        rest.into_iter().take(1).collect()
+    } else {
+        valid
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -66,7 +265,7 @@ struct MlveManifest {
 
 #[derive(Debug, Deserialize)]
 struct MlveCharacter {
-    #[allow(dead_code)]
+    /// Preview image of the character in the archive
     #[serde(default)]
     avatar: String,
     /// Costume entries
@@ -76,7 +275,7 @@ struct MlveCharacter {
 
 #[derive(Debug, Deserialize)]
 struct MlveCostume {
-    #[allow(dead_code)]
+    /// Display name of the costume
     #[serde(default)]
     name: String,
     /// Path to the costume file in the archive (hashed name with .bin3/.bin)
@@ -84,6 +283,18 @@ struct MlveCostume {
     path: String,
 }
 
+impl MlveManifest {
+    fn is_encrypted(&self) -> bool {
+        self.encrypt.as_deref() == Some("true")
+    }
+
+    fn is_stm(&self) -> bool {
+        self.format_type
+            .as_deref()
+            .is_some_and(|t| t.starts_with("STM"))
+    }
+}
+
 /// External config.json that accompanies STM-format LPK files
 #[derive(Debug, Deserialize, Default)]
 struct ExternalConfig {
@@ -98,30 +309,30 @@ fn extract_encrypted_lpk(
     lpk_path: &str,
     archive: &mut zip::ZipArchive<std::fs::File>,
     manifest: &MlveManifest,
-) -> Result<String, String> {
+    stm: &StmConfigSources,
+) -> Result<Vec<Costume>, String> {
     std::fs::create_dir_all(dest_dir).map_err(|e| e.to_string())?;
 
-    let is_encrypted = manifest
-        .encrypt
-        .as_deref()
-        .map(|s| s == "true")
-        .unwrap_or(false);
-
-    let is_stm = manifest
-        .format_type
-        .as_deref()
-        .map(|t| t.starts_with("STM"))
-        .unwrap_or(false);
+    let is_encrypted = manifest.is_encrypted();
+    let is_stm = manifest.is_stm();
 
     // Load external config.json for STM format
     let ext_config = if is_stm {
-        load_external_config(lpk_path)
+        find_external_config(lpk_path, stm)
     } else {
-        ExternalConfig::default()
+        None
     };
+    let has_config = ext_config.is_some();
+    let ext_config = ext_config.unwrap_or_default();
 
     let model_id = manifest.id.as_deref().unwrap_or("");
 
+    // A wrong key turns every entry into noise, so make sure a costume
+    // decrypts to JSON before writing anything
+    if is_encrypted {
+        verify_decryption(archive, manifest, &ext_config, has_config)?;
+    }
+
     // Collect all encrypted entry names from the archive
     let entry_names: Vec<String> = (0..archive.len())
         .filter_map(|i| archive.by_index(i).ok().map(|f| f.name().to_string()))
@@ -129,32 +340,41 @@ fn extract_encrypted_lpk(
 
     // Decrypt all .bin3/.bin files and track renamed files
     let mut rename_map: HashMap<String, String> = HashMap::new();
-    let manifest_hash = format!("{:x}", md5::compute(MANIFEST_NAME.as_bytes()));
 
     for entry_name in &entry_names {
         // Skip the manifest file
-        if entry_name == MANIFEST_NAME
-            || entry_name == &manifest_hash
-            || entry_name == &format!("{}.bin", manifest_hash)
-        {
+        if is_manifest_name(entry_name) {
             continue;
         }
 
         // Check if this is an encrypted entry (32 hex chars + .bin3 or .bin)
         let is_encrypted_entry = is_hashed_entry(entry_name);
 
-        let data = read_archive_entry(archive, entry_name)?;
-
-        let data = if is_encrypted && is_encrypted_entry {
+        let file = archive
+            .by_name(entry_name)
+            .map_err(|e| format!("{}: {}", entry_name, e))?;
+        if file.is_dir() {
+            continue;
+        }
+        let declared = file.size();
+        let mut reader: Box<dyn Read> = if is_encrypted && is_encrypted_entry {
             let key = derive_key(model_id, &ext_config, entry_name, is_stm);
-            decrypt_lcg_xor(&data, key)
+            Box::new(DecryptReader::new(file, key))
         } else {
-            data
+            Box::new(file)
         };
 
+        // Peek at the (decrypted) header to pick the output extension
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        reader
+            .by_ref()
+            .take(HEADER_LEN)
+            .read_to_end(&mut header)
+            .map_err(|e| format!("Failed to read {}: {}", entry_name, e))?;
+
         // Determine output filename based on file type detection
         let out_name = if is_encrypted_entry {
-            let ext = detect_extension(&data);
+            let ext = detect_extension(&header);
             let stem = entry_name
                 .strip_suffix(".bin3")
                 .or_else(|| entry_name.strip_suffix(".bin"))
@@ -166,64 +386,281 @@ fn extract_encrypted_lpk(
             entry_name.clone()
         };
 
-        let out_path = dest_dir.join(&out_name);
-        if let Some(parent) = out_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-        }
-        std::fs::write(&out_path, &data).map_err(|e| e.to_string())?;
+        let out_path = dest_dir.join(entry_path(&out_name)?);
+        write_entry(
+            &out_path,
+            header.as_slice().chain(reader),
+            declared,
+            entry_name,
+        )?;
     }
 
-    // Find the costume file (model descriptor) and save with correct extension
-    let mut model_json_path = None;
+    // Write each costume's descriptor under its own name. Several costumes
+    // may share a source file, so sources are only removed afterwards.
+    let base = sanitize_filename(manifest.name.as_deref().unwrap_or("model"));
+    let costume_count = manifest
+        .list
+        .iter()
+        .flat_map(|c| &c.costume)
+        .filter(|c| rename_map.contains_key(&c.path))
+        .count();
+    let mut costumes = Vec::new();
+    let mut sources = Vec::new();
+    let mut used_names = HashSet::new();
     for character in &manifest.list {
+        let avatar = resolve_avatar(dest_dir, &character.avatar, &rename_map);
         for costume in &character.costume {
-            if let Some(renamed) = rename_map.get(&costume.path) {
-                let src = dest_dir.join(renamed);
-                if src.exists() {
-                    // Read the model descriptor and rewrite file references
-                    let mut content =
-                        std::fs::read_to_string(&src).map_err(|e| e.to_string())?;
-                    for (old_name, new_name) in &rename_map {
-                        content = content.replace(old_name.as_str(), new_name.as_str());
-                    }
-
-                    // Detect Cubism version from content to use correct extension
-                    // Cubism 4/3: has "Version" and "FileReferences"
-                    // Cubism 2: has "model" and "textures"
-                    let is_cubism3plus = content.contains("\"FileReferences\"")
-                        || content.contains("\"Version\"");
-                    let ext = if is_cubism3plus {
-                        "model3.json"
-                    } else {
-                        "model.json"
-                    };
-
-                    let model_name = manifest
-                        .name
-                        .as_deref()
-                        .unwrap_or("model");
-                    let model_filename =
-                        format!("{}.{}", sanitize_filename(model_name), ext);
-                    let model_path = dest_dir.join(&model_filename);
-                    std::fs::write(&model_path, &content).map_err(|e| e.to_string())?;
-
-                    // Remove the original renamed file
-                    std::fs::remove_file(&src).ok();
-
-                    model_json_path =
-                        Some(model_path.to_string_lossy().to_string());
-                }
+            let Some(renamed) = rename_map.get(&costume.path) else {
+                continue;
+            };
+            let src = dest_dir.join(renamed);
+            if !src.exists() {
+                continue;
             }
+
+            // Read the model descriptor and rewrite file references
+            let mut content = std::fs::read_to_string(&src).map_err(|e| e.to_string())?;
+            for (old_name, new_name) in &rename_map {
+                content = content.replace(old_name.as_str(), new_name.as_str());
+            }
+
+            // Detect Cubism version from content to use correct extension
+            // Cubism 4/3: has "Version" and "FileReferences"
+            // Cubism 2: has "model" and "textures"
+            let is_cubism3plus =
+                content.contains("\"FileReferences\"") || content.contains("\"Version\"");
+            let ext = if is_cubism3plus {
+                "model3.json"
+            } else {
+                "model.json"
+            };
+
+            // A single costume keeps the plain model name
+            let stem = if costume_count == 1 {
+                base.clone()
+            } else if costume.name.is_empty() {
+                format!("{}_{}", base, costumes.len() + 1)
+            } else {
+                format!("{}_{}", base, sanitize_filename(&costume.name))
+            };
+            let mut model_filename = format!("{}.{}", stem, ext);
+            let mut n = 2;
+            while !used_names.insert(model_filename.clone()) {
+                model_filename = format!("{}_{}.{}", stem, n, ext);
+                n += 1;
+            }
+            let model_path = dest_dir.join(&model_filename);
+            std::fs::write(&model_path, &content).map_err(|e| e.to_string())?;
+            sources.push(src);
+
+            costumes.push(Costume {
+                path: model_path.to_string_lossy().to_string(),
+                name: Some(costume.name.clone()).filter(|n| !n.is_empty()),
+                avatar: avatar.clone(),
+            });
         }
     }
 
-    model_json_path.ok_or_else(|| "No model descriptor found in encrypted LPK".to_string())
+    // Remove the original renamed files
+    for src in sources {
+        std::fs::remove_file(src).ok();
+    }
+
+    if costumes.is_empty() {
+        return Err("No model descriptor found in encrypted LPK".to_string());
+    }
+    Ok(costumes)
+}
+
+/// Extracted path of a character's avatar image, if the archive has it.
+fn resolve_avatar(
+    dest_dir: &Path,
+    avatar: &str,
+    rename_map: &HashMap<String, String>,
+) -> Option<String> {
+    if avatar.is_empty() {
+        return None;
+    }
+    let name = rename_map.get(avatar).map(String::as_str).unwrap_or(avatar);
+    let path = dest_dir.join(entry_path(name).ok()?);
+    path.is_file().then(|| path.to_string_lossy().to_string())
+}
+
+/// Check that the first costume of an encrypted archive decrypts to JSON.
+fn verify_decryption(
+    archive: &mut zip::ZipArchive<std::fs::File>,
+    manifest: &MlveManifest,
+    ext_config: &ExternalConfig,
+    has_config: bool,
+) -> Result<(), String> {
+    let Some(path) = manifest
+        .list
+        .iter()
+        .flat_map(|c| &c.costume)
+        .map(|c| c.path.as_str())
+        .find(|p| is_hashed_entry(p) && archive.index_for_name(p).is_some())
+    else {
+        return Ok(());
+    };
+
+    let model_id = manifest.id.as_deref().unwrap_or("");
+    let key = derive_key(model_id, ext_config, path, manifest.is_stm());
+    let file = archive
+        .by_name(path)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    // Streamed rather than read with `read_archive_entry`: descriptors can be
+    // larger than MAX_METADATA_LEN, and `check_archive` already capped them
+    let reader = std::io::BufReader::new(DecryptReader::new(file, key));
+    if serde_json::from_reader::<_, serde::de::IgnoredAny>(reader).is_ok() {
+        return Ok(());
+    }
+    Err(match (manifest.is_stm(), has_config) {
+        (true, false) => format!("{}: no config.json found", STM_CONFIG_ERROR),
+        (true, true) => format!("{}: costume {} did not decrypt", STM_CONFIG_ERROR, path),
+        _ => format!("Failed to decrypt LPK: costume {} is not valid JSON", path),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Inspection
+// ---------------------------------------------------------------------------
+
+/// Summary of an LPK's contents, gathered without extracting it.
+#[derive(Debug, Clone, Serialize)]
+pub struct LpkInfo {
+    /// "STM_1_0", "STD_1_0", "STD_2_0", ... or "plain" without a manifest
+    pub format: String,
+    pub encrypted: bool,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub characters: Vec<CharacterInfo>,
+    /// Number of file entries
+    pub entry_count: usize,
+    /// Sum of uncompressed entry sizes, in bytes
+    pub total_size: u64,
+    /// Number of entries per detected file type, e.g. "png" or "moc3"
+    pub asset_types: BTreeMap<String, usize>,
+    /// Whether the config.json needed to decrypt STM archives was found
+    pub stm_config_found: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CharacterInfo {
+    pub avatar: Option<String>,
+    pub costumes: Vec<CostumeInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CostumeInfo {
+    pub name: Option<String>,
+    /// Entry name of the costume descriptor in the archive
+    pub path: String,
+}
+
+/// Describe the LPK at `lpk_path`. Only the manifest and the first
+/// `HEADER_LEN` bytes of each entry are read; nothing is written to disk.
+pub fn inspect_lpk(lpk_path: &str, stm: &StmConfigSources) -> Result<LpkInfo, String> {
+    let file = std::fs::File::open(lpk_path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let manifest = read_manifest(&mut archive);
+
+    let encrypted = manifest.as_ref().is_some_and(|m| m.is_encrypted());
+    let is_stm = manifest.as_ref().is_some_and(|m| m.is_stm());
+    let ext_config = if is_stm {
+        find_external_config(lpk_path, stm)
+    } else {
+        None
+    };
+    let stm_config_found = ext_config.is_some();
+    let ext_config = ext_config.unwrap_or_default();
+    let model_id = manifest
+        .as_ref()
+        .and_then(|m| m.id.as_deref())
+        .unwrap_or("");
+
+    let mut entry_count = 0;
+    let mut total_size: u64 = 0;
+    let mut asset_types = BTreeMap::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(|e| e.to_string())?;
+        if file.is_dir() || (manifest.is_some() && is_manifest_name(file.name())) {
+            continue;
+        }
+        entry_count += 1;
+        total_size = total_size.saturating_add(file.size());
+
+        let name = file.name().to_string();
+        let mut reader: Box<dyn Read> = if encrypted && is_hashed_entry(&name) {
+            let key = derive_key(model_id, &ext_config, &name, is_stm);
+            Box::new(DecryptReader::new(file, key))
+        } else {
+            Box::new(file)
+        };
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        reader
+            .by_ref()
+            .take(HEADER_LEN)
+            .read_to_end(&mut header)
+            .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        *asset_types
+            .entry(detect_extension(&header).to_string())
+            .or_insert(0) += 1;
+    }
+
+    let characters = manifest
+        .as_ref()
+        .map(|m| {
+            m.list
+                .iter()
+                .map(|c| CharacterInfo {
+                    avatar: Some(c.avatar.clone()).filter(|a| !a.is_empty()),
+                    costumes: c
+                        .costume
+                        .iter()
+                        .map(|cos| CostumeInfo {
+                            name: Some(cos.name.clone()).filter(|n| !n.is_empty()),
+                            path: cos.path.clone(),
+                        })
+                        .collect(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(LpkInfo {
+        format: manifest
+            .as_ref()
+            .and_then(|m| m.format_type.clone())
+            .unwrap_or_else(|| "plain".to_string()),
+        encrypted,
+        id: manifest.as_ref().and_then(|m| m.id.clone()),
+        name: manifest.as_ref().and_then(|m| m.name.clone()),
+        characters,
+        entry_count,
+        total_size,
+        asset_types,
+        stm_config_found,
+    })
+}
+
+/// Whether the archive at `lpk_path` is in an STM format, and so needs a
+/// config.json to decrypt. Reads only the manifest.
+pub fn is_stm_archive(lpk_path: &str) -> Result<bool, String> {
+    let file = std::fs::File::open(lpk_path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    Ok(read_manifest(&mut archive).is_some_and(|m| m.is_stm()))
 }
 
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
+/// Whether `name` is the manifest entry, under its plain or hashed name
+fn is_manifest_name(name: &str) -> bool {
+    let hashed = format!("{:x}", md5::compute(MANIFEST_NAME.as_bytes()));
+    name == MANIFEST_NAME || name == hashed || name == format!("{}.bin", hashed)
+}
+
 /// Check if a ZIP entry name looks like an encrypted file (32 hex chars + .bin3 or .bin)
 fn is_hashed_entry(name: &str) -> bool {
     let stem = name
@@ -249,7 +686,10 @@ fn derive_key(model_id: &str, ext_config: &ExternalConfig, entry_name: &str, is_
     java_hash_code(&key_str)
 }
 
-/// Detect file type by magic bytes and return appropriate extension
+/// Detect file type by magic bytes and return appropriate extension.
+///
+/// `data` may be just the start of the file; a UTF-8 sequence cut off at
+/// the end does not prevent text detection.
 fn detect_extension(data: &[u8]) -> &'static str {
     if data.len() >= 4 {
         // PNG: 89 50 4E 47
@@ -284,7 +724,12 @@ fn detect_extension(data: &[u8]) -> &'static str {
         }
     }
     // Try to detect text-based formats
-    if let Ok(text) = std::str::from_utf8(data) {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => Some(text),
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&data[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    };
+    if let Some(text) = text {
         let trimmed = text.trim_start();
         if trimmed.starts_with('{') || trimmed.starts_with('[') {
             return "json";
@@ -316,7 +761,7 @@ fn sanitize_filename(name: &str) -> String {
 
 fn read_manifest(archive: &mut zip::ZipArchive<std::fs::File>) -> Option<MlveManifest> {
     // Try the plain name first
-    if let Ok(data) = read_archive_entry(archive, MANIFEST_NAME) {
+    if let Ok(data) = read_archive_entry(archive, MANIFEST_NAME, None) {
         if let Ok(manifest) = serde_json::from_slice::<MlveManifest>(&data) {
             return Some(manifest);
         }
@@ -325,7 +770,7 @@ fn read_manifest(archive: &mut zip::ZipArchive<std::fs::File>) -> Option<MlveMan
     // Try MD5-hashed name (with and without .bin extension)
     let hashed_name = format!("{:x}", md5::compute(MANIFEST_NAME.as_bytes()));
     for name in [format!("{}.bin", hashed_name), hashed_name] {
-        if let Ok(data) = read_archive_entry(archive, &name) {
+        if let Ok(data) = read_archive_entry(archive, &name, None) {
             if let Ok(manifest) = serde_json::from_slice::<MlveManifest>(&data) {
                 return Some(manifest);
             }
@@ -335,16 +780,28 @@ fn read_manifest(archive: &mut zip::ZipArchive<std::fs::File>) -> Option<MlveMan
     None
 }
 
+/// Read a small metadata entry (e.g. the manifest) into memory, decrypting
+/// it with `key` if given and refusing entries larger than `MAX_METADATA_LEN`.
 fn read_archive_entry(
     archive: &mut zip::ZipArchive<std::fs::File>,
     name: &str,
+    key: Option<i64>,
 ) -> Result<Vec<u8>, String> {
-    let mut file = archive
+    let file = archive
         .by_name(name)
         .map_err(|e| format!("{}: {}", name, e))?;
-    let mut data = Vec::with_capacity(file.size() as usize);
-    file.read_to_end(&mut data)
+    let reader: Box<dyn Read> = match key {
+        Some(key) => Box::new(DecryptReader::new(file, key)),
+        None => Box::new(file),
+    };
+    let mut data = Vec::new();
+    reader
+        .take(MAX_METADATA_LEN + 1)
+        .read_to_end(&mut data)
         .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    if data.len() as u64 > MAX_METADATA_LEN {
+        return Err(format!("{} is too large", name));
+    }
     Ok(data)
 }
 
@@ -352,21 +809,80 @@ fn read_archive_entry(
 // External config.json loader (for STM format)
 // ---------------------------------------------------------------------------
 
-fn load_external_config(lpk_path: &str) -> ExternalConfig {
-    let lpk = Path::new(lpk_path);
-    if let Some(parent) = lpk.parent() {
-        let config_path = parent.join("config.json");
-        if config_path.exists() {
-            if let Ok(data) = std::fs::read_to_string(&config_path) {
-                if let Ok(config) = serde_json::from_str::<ExternalConfig>(&data) {
-                    eprintln!("[rive2d] Loaded external config.json for STM decryption");
-                    return config;
+/// Prefix of errors caused by a missing or wrong STM config.json, so
+/// callers can ask the user for one.
+pub const STM_CONFIG_ERROR: &str = "Missing or incorrect STM config.json";
+
+/// Where to look for the config.json that STM archives are keyed with,
+/// besides next to the archive and in its Steam workshop item folder.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StmConfigSources {
+    /// Config chosen for this particular archive; checked first
+    pub override_path: Option<PathBuf>,
+    /// Config (or directory containing one) set by the user for all imports
+    pub user_path: Option<PathBuf>,
+}
+
+impl StmConfigSources {
+    /// Candidate config files for `lpk_path`, in order of precedence.
+    fn candidates(&self, lpk_path: &Path) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .override_path
+            .iter()
+            .chain(&self.user_path)
+            .map(|p| {
+                if p.is_dir() {
+                    p.join("config.json")
+                } else {
+                    p.clone()
                 }
-            }
+            })
+            .collect();
+        if let Some(parent) = lpk_path.parent() {
+            paths.push(parent.join("config.json"));
         }
+        if let Some(item) = workshop_item_dir(lpk_path) {
+            paths.push(item.join("config.json"));
+        }
+        paths
+    }
+}
+
+/// The first config.json with key material found through `sources`.
+fn find_external_config(lpk_path: &str, sources: &StmConfigSources) -> Option<ExternalConfig> {
+    let found = sources
+        .candidates(Path::new(lpk_path))
+        .into_iter()
+        .find_map(|path| {
+            let data = std::fs::read_to_string(&path).ok()?;
+            let config = serde_json::from_str::<ExternalConfig>(&data).ok()?;
+            (!config.file_id.is_empty()).then_some((path, config))
+        });
+    match found {
+        Some((path, config)) => {
+            eprintln!("[rive2d] Using {} for STM decryption", path.display());
+            Some(config)
+        }
+        None => {
+            eprintln!("[rive2d] Warning: No config.json found for STM format LPK");
+            None
+        }
+    }
+}
+
+/// Root of the Steam workshop item containing `lpk_path`
+/// (`.../workshop/content/<app id>/<item id>`), if it is in one.
+fn workshop_item_dir(lpk_path: &Path) -> Option<PathBuf> {
+    let components: Vec<_> = lpk_path.components().collect();
+    let start = components
+        .windows(2)
+        .position(|w| w[0].as_os_str() == "workshop" && w[1].as_os_str() == "content")?;
+    // The archive itself must be below the item directory
+    let item_end = start + 4;
+    if components.len() <= item_end {
+        return None;
     }
-    eprintln!("[rive2d] Warning: No config.json found for STM format LPK, decryption may fail");
-    ExternalConfig::default()
+    Some(components[..item_end].iter().collect())
 }
 
 // ---------------------------------------------------------------------------
@@ -374,7 +890,7 @@ fn load_external_config(lpk_path: &str) -> ExternalConfig {
 // ---------------------------------------------------------------------------
 
 /// Implements Java's `String.hashCode()` with sign extension to i64.
-fn java_hash_code(s: &str) -> i64 {
+pub(crate) fn java_hash_code(s: &str) -> i64 {
     let mut hash: i32 = 0;
     for c in s.encode_utf16() {
         hash = hash.wrapping_mul(31).wrapping_add(c as i32);
@@ -386,21 +902,73 @@ fn java_hash_code(s: &str) -> i64 {
 // LCG XOR cipher
 // ---------------------------------------------------------------------------
 
-/// Decrypt data using LCG-based XOR stream cipher.
+/// Keystream size after which the cipher state resets to the key
+const CIPHER_CHUNK: usize = 1024;
+
+/// State of the LCG-based XOR stream cipher.
 ///
 /// The state is replaced by the shifted+masked output each iteration:
 ///   state = (65535 & ((2531011 + 214013 * state) >> 16))
 ///   byte ^= state & 0xFF
 /// State resets to `key` at the start of every 1024-byte chunk.
-fn decrypt_lcg_xor(data: &[u8], key: i64) -> Vec<u8> {
-    let mut result = Vec::with_capacity(data.len());
-    for chunk in data.chunks(1024) {
-        let mut k = key;
-        for &byte in chunk {
-            k = (65535 & ((2531011 + 214013 * k) >> 16)) & 0xFFFFFFFF;
-            result.push((k as u8) ^ byte);
+struct LcgXor {
+    key: i64,
+    state: i64,
+    /// Position within the current chunk
+    offset: usize,
+}
+
+impl LcgXor {
+    fn new(key: i64) -> Self {
+        Self {
+            key,
+            state: key,
+            offset: 0,
+        }
+    }
+
+    /// XOR `buf` in place with the next `buf.len()` keystream bytes.
+    fn apply(&mut self, buf: &mut [u8]) {
+        for byte in buf {
+            if self.offset == CIPHER_CHUNK {
+                self.state = self.key;
+                self.offset = 0;
+            }
+            self.state = (65535 & ((2531011 + 214013 * self.state) >> 16)) & 0xFFFFFFFF;
+            *byte ^= self.state as u8;
+            self.offset += 1;
         }
     }
+}
+
+/// `Read` adapter that decrypts an LCG XOR stream as it is read. The cipher
+/// is symmetric, so it also encrypts.
+pub(crate) struct DecryptReader<R> {
+    inner: R,
+    cipher: LcgXor,
+}
+
+impl<R: Read> DecryptReader<R> {
+    pub(crate) fn new(inner: R, key: i64) -> Self {
+        Self {
+            inner,
+            cipher: LcgXor::new(key),
+        }
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.cipher.apply(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Decrypt a whole buffer (the cipher is symmetric, so this also encrypts).
+pub(crate) fn decrypt_lcg_xor(data: &[u8], key: i64) -> Vec<u8> {
+    let mut result = data.to_vec();
+    LcgXor::new(key).apply(&mut result);
     result
 }
 
@@ -408,22 +976,104 @@ fn decrypt_lcg_xor(data: &[u8], key: i64) -> Vec<u8> {
 // Model JSON finder (for regular LPK)
 // ---------------------------------------------------------------------------
 
-pub fn find_model_json(dir: &Path) -> Option<String> {
-    let entries = std::fs::read_dir(dir).ok()?;
-    for entry in entries {
-        let entry = entry.ok()?;
+/// Whether `name` is a Cubism model descriptor file name.
+pub fn is_model_json(name: &str) -> bool {
+    name.ends_with(".model3.json") || name.ends_with(".model.json")
+}
+
+/// A model descriptor found by `find_model_candidates`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModelCandidate {
+    pub path: String,
+    /// From the descriptor's content, or its file name if it doesn't parse
+    pub version: CubismVersion,
+    /// Number of directories between the search root and the descriptor
+    pub depth: usize,
+    pub texture_count: usize,
+    /// Whether the descriptor parses
+    pub valid: bool,
+}
+
+impl ModelCandidate {
+    fn load(path: &Path, depth: usize) -> Self {
+        let desc = ModelDescriptor::load(path).ok();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let version = match &desc {
+            Some(desc) => desc.version,
+            None if name.ends_with(".model3.json") => CubismVersion::Cubism3,
+            None => CubismVersion::Cubism2,
+        };
+        Self {
+            path: path.to_string_lossy().to_string(),
+            version,
+            depth,
+            texture_count: desc.as_ref().map_or(0, |d| d.textures.len()),
+            valid: desc.is_some(),
+        }
+    }
+
+    /// Ranking key, best first: valid descriptors, then Cubism 3+, then
+    /// shallower paths, then more textures, then path order.
+    fn rank(&self) -> impl Ord + '_ {
+        (
+            !self.valid,
+            self.version != CubismVersion::Cubism3,
+            self.depth,
+            std::cmp::Reverse(self.texture_count),
+            &self.path,
+        )
+    }
+}
+
+/// Every model descriptor under `dir`, best first.
+pub fn find_model_candidates(dir: &Path) -> Vec<ModelCandidate> {
+    let mut out = Vec::new();
+    collect_candidates(dir, 0, &mut out);
+    out.sort_by(|a, b| a.rank().cmp(&b.rank()));
+    out
+}
+
+fn collect_candidates(dir: &Path, depth: usize, out: &mut Vec<ModelCandidate>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
         let path = entry.path();
-        if path.is_dir() {
-            if let Some(found) = find_model_json(&path) {
-                return Some(found);
-            }
-        } else if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-            if name.ends_with(".model3.json") || name.ends_with(".model.json") {
-                return Some(path.to_string_lossy().to_string());
-            }
+        // Symlinked directories could loop
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            collect_candidates(&path, depth + 1, out);
+        } else if path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(is_model_json)
+        {
+            out.push(ModelCandidate::load(&path, depth));
         }
     }
-    None
+}
+
+/// Path of the best model descriptor under `dir`.
+pub fn find_model_json(dir: &Path) -> Option<String> {
+    find_model_candidates(dir)
+        .into_iter()
+        .next()
+        .map(|c| c.path)
+}
+
+/// Write a zip with `entries`, for tests of archive imports.
+#[cfg(test)]
+pub(crate) fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+    use std::io::Write;
+    let options = zip::write::SimpleFileOptions::default();
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+    for (name, data) in entries {
+        zip.start_file(*name, options).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
 }
 
 #[cfg(test)]
@@ -446,6 +1096,545 @@ mod tests {
         assert_eq!(decrypted, original);
     }
 
+    /// Reader that returns at most `step` bytes per call.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.step.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_decrypt_reader_matches_buffer() {
+        let original: Vec<u8> = (0..5000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let key = java_hash_code("stream_key");
+        let encrypted = decrypt_lcg_xor(&original, key);
+
+        // Read boundaries must not line up with the 1024-byte cipher chunks
+        for step in [1, 333, 1024, 4096] {
+            let mut out = Vec::new();
+            DecryptReader::new(
+                Trickle {
+                    data: &encrypted,
+                    step,
+                },
+                key,
+            )
+            .read_to_end(&mut out)
+            .unwrap();
+            assert_eq!(out, original, "step {}", step);
+        }
+    }
+
+    const MODEL_ENTRY: &str = "0123456789abcdef0123456789abcdef.bin3";
+    const TEXTURE_ENTRY: &str = "fedcba9876543210fedcba9876543210.bin";
+
+    /// Write an STD_1_0 encrypted LPK, returning the plain model and texture.
+    fn write_encrypted_lpk(lpk_path: &Path) -> (String, Vec<u8>) {
+        // Texture spans several cipher chunks
+        let mut texture = vec![0x89, 0x50, 0x4E, 0x47];
+        texture.extend((0..3000u32).map(|i| i as u8));
+        let model = format!(
+            r#"{{"Version":3,"FileReferences":{{"Textures":["{}"]}}}}"#,
+            TEXTURE_ENTRY
+        );
+        let manifest = format!(
+            r#"{{"type":"STD_1_0","encrypt":"true","id":"m1","name":"Test",
+                "list":[{{"costume":[{{"path":"{}"}}]}}]}}"#,
+            MODEL_ENTRY
+        );
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(lpk_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file(MANIFEST_NAME, options).unwrap();
+        zip.write_all(manifest.as_bytes()).unwrap();
+        for (name, data) in [
+            (MODEL_ENTRY, model.as_bytes()),
+            (TEXTURE_ENTRY, &texture[..]),
+        ] {
+            zip.start_file(name, options).unwrap();
+            let key = java_hash_code(&format!("m1{}", name));
+            zip.write_all(&decrypt_lcg_xor(data, key)).unwrap();
+        }
+        zip.finish().unwrap();
+        (model, texture)
+    }
+
+    #[test]
+    fn test_extract_encrypted_lpk() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lpk_path = tmp.path().join("a.lpk");
+        let (model, texture) = write_encrypted_lpk(&lpk_path);
+
+        let dest = tmp.path().join("out");
+        let models = extract_lpk(
+            &dest,
+            lpk_path.to_str().unwrap(),
+            &ExtractLimits::default(),
+            &StmConfigSources::default(),
+        )
+        .unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].len(), 1);
+        let model_path = &models[0][0].path;
+        assert!(model_path.ends_with("Test.model3.json"));
+        assert_eq!(
+            std::fs::read_to_string(model_path).unwrap(),
+            model.replace(".bin\"", ".png\"")
+        );
+        assert_eq!(
+            std::fs::read(dest.join("fedcba9876543210fedcba9876543210.png")).unwrap(),
+            texture
+        );
+    }
+
+    #[test]
+    fn test_extract_encrypted_lpk_large_descriptor() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lpk_path = tmp.path().join("a.lpk");
+        let model = format!(
+            r#"{{"Version":3,"Pad":"{}"}}"#,
+            "x".repeat(MAX_METADATA_LEN as usize)
+        );
+        let manifest = format!(
+            r#"{{"type":"STD_1_0","encrypt":"true","id":"m1","name":"Big",
+                "list":[{{"costume":[{{"path":"{}"}}]}}]}}"#,
+            MODEL_ENTRY
+        );
+        let key = java_hash_code(&format!("m1{}", MODEL_ENTRY));
+        let encrypted = decrypt_lcg_xor(model.as_bytes(), key);
+        write_zip(
+            &lpk_path,
+            &[
+                (MANIFEST_NAME, manifest.as_bytes()),
+                (MODEL_ENTRY, &encrypted),
+            ],
+        );
+
+        let models = extract_lpk(
+            &tmp.path().join("out"),
+            lpk_path.to_str().unwrap(),
+            &ExtractLimits::default(),
+            &StmConfigSources::default(),
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&models[0][0].path).unwrap(), model);
+    }
+
+    /// Write an STM_1_0 LPK keyed with `file_id`/`meta`.
+    fn write_stm_lpk(lpk_path: &Path, file_id: &str, meta: &str) {
+        let manifest = format!(
+            r#"{{"type":"STM_1_0","encrypt":"true","id":"m1","name":"Stm",
+                "list":[{{"costume":[{{"path":"{}"}}]}}]}}"#,
+            MODEL_ENTRY
+        );
+        let key = java_hash_code(&format!("m1{}{}{}", file_id, MODEL_ENTRY, meta));
+        let model = decrypt_lcg_xor(br#"{"Version":3}"#, key);
+        write_zip(
+            lpk_path,
+            &[(MANIFEST_NAME, manifest.as_bytes()), (MODEL_ENTRY, &model)],
+        );
+    }
+
+    fn write_stm_config(path: &Path, file_id: &str, meta: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let config = format!(r#"{{"fileId":"{}","metaData":"{}"}}"#, file_id, meta);
+        std::fs::write(path, config).unwrap();
+    }
+
+    #[test]
+    fn test_workshop_item_dir() {
+        let lpk = Path::new("/steam/steamapps/workshop/content/616720/123/sub/a.lpk");
+        assert_eq!(
+            workshop_item_dir(lpk).unwrap(),
+            Path::new("/steam/steamapps/workshop/content/616720/123")
+        );
+        assert!(workshop_item_dir(Path::new("/steam/workshop/content/616720")).is_none());
+        assert!(workshop_item_dir(Path::new("/home/a.lpk")).is_none());
+    }
+
+    #[test]
+    fn test_stm_config_from_workshop_item() {
+        let tmp = tempfile::tempdir().unwrap();
+        let item = tmp.path().join("workshop/content/616720/42");
+        std::fs::create_dir_all(item.join("files")).unwrap();
+        let lpk_path = item.join("files/a.lpk");
+        write_stm_lpk(&lpk_path, "f1", "meta");
+        write_stm_config(&item.join("config.json"), "f1", "meta");
+
+        let dest = tmp.path().join("out");
+        let models = extract_lpk(
+            &dest,
+            lpk_path.to_str().unwrap(),
+            &ExtractLimits::default(),
+            &StmConfigSources::default(),
+        )
+        .unwrap();
+        assert!(models[0][0].path.ends_with("Stm.model3.json"));
+        let info = inspect_lpk(lpk_path.to_str().unwrap(), &StmConfigSources::default()).unwrap();
+        assert!(info.stm_config_found);
+        assert!(is_stm_archive(lpk_path.to_str().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_stm_config_errors_and_override() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lpk_path = tmp.path().join("a.lpk");
+        write_stm_lpk(&lpk_path, "f1", "meta");
+        let extract = |stm: &StmConfigSources| {
+            let dest = tmp.path().join("out");
+            let result = extract_lpk(
+                &dest,
+                lpk_path.to_str().unwrap(),
+                &ExtractLimits::default(),
+                stm,
+            );
+            std::fs::remove_dir_all(&dest).ok();
+            result
+        };
+
+        let err = extract(&StmConfigSources::default()).unwrap_err();
+        assert!(err.starts_with(STM_CONFIG_ERROR), "{}", err);
+        assert!(err.contains("no config.json"), "{}", err);
+
+        // A wrong sibling config is reported instead of extracting garbage
+        write_stm_config(&tmp.path().join("config.json"), "wrong", "meta");
+        let err = extract(&StmConfigSources::default()).unwrap_err();
+        assert!(err.contains("did not decrypt"), "{}", err);
+        assert!(!tmp.path().join("out").exists());
+
+        // The per-archive override wins over the user path and the sibling
+        let good = tmp.path().join("good/config.json");
+        write_stm_config(&good, "f1", "meta");
+        let stm = StmConfigSources {
+            override_path: Some(good),
+            user_path: Some(tmp.path().to_path_buf()),
+        };
+        assert!(extract(&stm).is_ok());
+        let stm = StmConfigSources {
+            override_path: Some(tmp.path().join("config.json")),
+            user_path: Some(tmp.path().join("good")),
+        };
+        assert!(extract(&stm).is_err());
+        let stm = StmConfigSources {
+            override_path: None,
+            user_path: Some(tmp.path().join("good")),
+        };
+        assert!(extract(&stm).is_ok());
+    }
+
+    #[test]
+    fn test_inspect_lpk() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lpk_path = tmp.path().join("a.lpk");
+        let (model, texture) = write_encrypted_lpk(&lpk_path);
+
+        let info = inspect_lpk(lpk_path.to_str().unwrap(), &StmConfigSources::default()).unwrap();
+        assert_eq!(info.format, "STD_1_0");
+        assert!(info.encrypted);
+        assert_eq!(info.id.as_deref(), Some("m1"));
+        assert_eq!(info.name.as_deref(), Some("Test"));
+        assert_eq!(info.characters.len(), 1);
+        assert_eq!(info.characters[0].costumes[0].path, MODEL_ENTRY);
+        assert_eq!(info.entry_count, 2);
+        assert_eq!(info.total_size, (model.len() + texture.len()) as u64);
+        assert_eq!(
+            info.asset_types,
+            BTreeMap::from([("json".to_string(), 1), ("png".to_string(), 1)])
+        );
+        assert!(!info.stm_config_found);
+        assert!(!is_stm_archive(lpk_path.to_str().unwrap()).unwrap());
+        // Nothing was extracted next to the archive
+        assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 1);
+
+        let plain = tmp.path().join("plain.lpk");
+        write_zip(&plain, &[("m/", b""), ("m/a.model3.json", b"{}")]);
+        let info = inspect_lpk(plain.to_str().unwrap(), &StmConfigSources::default()).unwrap();
+        assert_eq!(info.format, "plain");
+        assert!(!info.encrypted);
+        assert!(info.characters.is_empty());
+        assert_eq!(info.entry_count, 1);
+    }
+
+    #[test]
+    fn test_extract_costumes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lpk_path = tmp.path().join("a.lpk");
+        let summer = "00000000000000000000000000000001.bin3";
+        let winter = "00000000000000000000000000000002.bin3";
+        let avatar = "00000000000000000000000000000003.bin";
+        let manifest = format!(
+            r#"{{"type":"STD_1_0","encrypt":"false","id":"m1","name":"Test","list":[
+                {{"avatar":"{avatar}","costume":[{{"name":"Summer","path":"{summer}"}},
+                    {{"name":"Winter","path":"{winter}"}}]}},
+                {{"costume":[{{"name":"Summer","path":"{summer}"}},{{"path":"{winter}"}}]}}]}}"#
+        );
+        write_zip(
+            &lpk_path,
+            &[
+                (MANIFEST_NAME, manifest.as_bytes()),
+                (
+                    summer,
+                    br#"{"Version":3,"FileReferences":{"Moc":"summer.moc3"}}"#,
+                ),
+                (winter, br#"{"model":"winter.moc","textures":[]}"#),
+                (avatar, &[0x89, 0x50, 0x4E, 0x47, 0x00]),
+            ],
+        );
+
+        let dest = tmp.path().join("out");
+        let mut models = extract_lpk(
+            &dest,
+            lpk_path.to_str().unwrap(),
+            &ExtractLimits::default(),
+            &StmConfigSources::default(),
+        )
+        .unwrap();
+        // Costumes are variants of one model
+        assert_eq!(models.len(), 1);
+        let costumes = models.remove(0);
+        let names: Vec<_> = costumes
+            .iter()
+            .map(|c| {
+                let file = Path::new(&c.path).file_name().unwrap().to_string_lossy();
+                (file.to_string(), c.name.clone())
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (
+                    "Test_Summer.model3.json".to_string(),
+                    Some("Summer".to_string())
+                ),
+                (
+                    "Test_Winter.model.json".to_string(),
+                    Some("Winter".to_string())
+                ),
+                (
+                    "Test_Summer_2.model3.json".to_string(),
+                    Some("Summer".to_string())
+                ),
+                ("Test_4.model.json".to_string(), None),
+            ]
+        );
+        let avatar_path = dest.join("00000000000000000000000000000003.png");
+        assert_eq!(
+            costumes[0].avatar.as_deref(),
+            Some(avatar_path.to_str().unwrap())
+        );
+        assert_eq!(costumes[2].avatar, None);
+        // Shared sources are removed only after every costume was written
+        assert!(!dest.join("00000000000000000000000000000001.json").exists());
+        assert!(std::fs::read_to_string(&costumes[2].path)
+            .unwrap()
+            .contains("summer.moc3"));
+    }
+
+    fn extract_crafted(
+        entries: &[(&str, &[u8])],
+        limits: &ExtractLimits,
+    ) -> Result<String, String> {
+        let tmp = tempfile::tempdir().unwrap();
+        let lpk_path = tmp.path().join("a.lpk");
+        write_zip(&lpk_path, entries);
+        let dest = tmp.path().join("out");
+        let result = extract_lpk(
+            &dest,
+            lpk_path.to_str().unwrap(),
+            limits,
+            &StmConfigSources::default(),
+        )
+        .map(|models| models[0][0].path.clone());
+        if result.is_err() {
+            assert!(!dest.exists(), "rejected archive left files behind");
+        }
+        assert!(!tmp.path().join("evil.txt").exists());
+        result
+    }
+
+    #[test]
+    fn test_entry_path() {
+        assert_eq!(entry_path("a/./b.png").unwrap(), Path::new("a/b.png"));
+        for name in [
+            "../evil.txt",
+            "a/../../evil.txt",
+            "/etc/passwd",
+            "\\evil.txt",
+            "C:/evil.txt",
+            "",
+        ] {
+            assert!(entry_path(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_extract_regular_lpk() {
+        let model: &[u8] = b"{}";
+        let limits = ExtractLimits::default();
+        let path = extract_crafted(&[("m/a.model3.json", model)], &limits).unwrap();
+        assert!(path.ends_with("a.model3.json"));
+    }
+
+    #[test]
+    fn test_find_model_candidates_ranking() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let v3 = |textures: &[&str]| {
+            serde_json::json!({"Version": 3, "FileReferences": {"Moc": "a.moc3", "Textures": textures}})
+                .to_string()
+        };
+        let v2 = serde_json::json!({"model": "a.moc", "textures": ["t.png"]}).to_string();
+        std::fs::create_dir_all(root.join("deep/er")).unwrap();
+        for (file, data) in [
+            ("legacy.model.json", v2.clone()),
+            ("broken.model3.json", "{".to_string()),
+            ("deep/b.model3.json", v3(&["t.png"])),
+            ("deep/a.model3.json", v3(&["t.png"])),
+            ("deep/more.model3.json", v3(&["t.png", "u.png"])),
+            ("deep/er/top.model3.json", v3(&["t.png", "u.png", "v.png"])),
+        ] {
+            std::fs::write(root.join(file), data).unwrap();
+        }
+
+        let ranked: Vec<_> = find_model_candidates(root)
+            .into_iter()
+            .map(|c| {
+                Path::new(&c.path)
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        assert_eq!(
+            ranked,
+            [
+                "deep/more.model3.json",
+                "deep/a.model3.json",
+                "deep/b.model3.json",
+                "deep/er/top.model3.json",
+                "legacy.model.json",
+                "broken.model3.json",
+            ]
+        );
+        assert!(find_model_json(root)
+            .unwrap()
+            .ends_with("deep/more.model3.json"));
+        assert_eq!(find_model_json(&root.join("missing")), None);
+    }
+
+    #[test]
+    fn test_extract_regular_lpk_models() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lpk_path = tmp.path().join("a.lpk");
+        let v3 = br#"{"Version":3,"FileReferences":{"Moc":"a.moc3"}}"#;
+        let v2 = br#"{"model":"a.moc","textures":[]}"#;
+        write_zip(
+            &lpk_path,
+            &[
+                ("a/a.model.json", v2),
+                ("a/a.model3.json", v3),
+                ("b/b.model3.json", v3),
+                ("c/c.model.json", v2),
+            ],
+        );
+        let dest = tmp.path().join("out");
+        let models = extract_lpk(
+            &dest,
+            lpk_path.to_str().unwrap(),
+            &ExtractLimits::default(),
+            &StmConfigSources::default(),
+        )
+        .unwrap();
+        // Separate models, not costumes of one
+        assert!(models.iter().all(|costumes| costumes.len() == 1));
+        let paths: Vec<_> = models.iter().map(|c| c[0].path.as_str()).collect();
+        assert_eq!(paths.len(), 3);
+        assert!(paths[0].ends_with("a/a.model3.json"));
+        assert!(paths[1].ends_with("b/b.model3.json"));
+        assert!(paths[2].ends_with("c/c.model.json"));
+    }
+
+    #[test]
+    fn test_rejects_zip_slip() {
+        let limits = ExtractLimits::default();
+        for name in ["../evil.txt", "m/../../evil.txt", "/evil.txt"] {
+            let err =
+                extract_crafted(&[("a.model3.json", b"{}"), (name, b"x")], &limits).unwrap_err();
+            assert!(err.contains("unsafe name"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_rejects_symlink_entry() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lpk_path = tmp.path().join("a.lpk");
+        let options = zip::write::SimpleFileOptions::default();
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&lpk_path).unwrap());
+        zip.add_symlink("link", "/etc", options).unwrap();
+        zip.finish().unwrap();
+
+        let dest = tmp.path().join("out");
+        let err = extract_lpk(
+            &dest,
+            lpk_path.to_str().unwrap(),
+            &ExtractLimits::default(),
+            &StmConfigSources::default(),
+        )
+        .unwrap_err();
+        assert!(err.contains("symbolic link"), "{}", err);
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_rejects_archive_bombs() {
+        let zeros = vec![0u8; 2 * 1024 * 1024];
+        let err = extract_crafted(&[("a.json", &zeros)], &ExtractLimits::default()).unwrap_err();
+        assert!(err.contains("compression ratio"), "{}", err);
+
+        let limits = ExtractLimits {
+            max_total_size: 10,
+            ..ExtractLimits::default()
+        };
+        let err =
+            extract_crafted(&[("a.json", b"123456"), ("b.json", b"123456")], &limits).unwrap_err();
+        assert!(err.contains("more than the limit of 10 bytes"), "{}", err);
+
+        let limits = ExtractLimits {
+            max_entries: 1,
+            ..ExtractLimits::default()
+        };
+        let err = extract_crafted(&[("a.json", b"{}"), ("b.json", b"{}")], &limits).unwrap_err();
+        assert!(err.contains("2 entries"), "{}", err);
+    }
+
+    #[test]
+    fn test_rejected_archive_keeps_previous_extraction() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("out");
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(dest.join("a.model3.json"), "{}").unwrap();
+
+        let lpk_path = tmp.path().join("a.lpk");
+        write_zip(&lpk_path, &[("../evil.txt", b"x")]);
+        assert!(extract_lpk(
+            &dest,
+            lpk_path.to_str().unwrap(),
+            &ExtractLimits::default(),
+            &StmConfigSources::default()
+        )
+        .is_err());
+        assert!(dest.join("a.model3.json").exists());
+    }
+
     #[test]
     fn test_md5_manifest_name() {
         let hash = format!("{:x}", md5::compute(MANIFEST_NAME.as_bytes()));
@@ -467,5 +1656,7 @@ mod tests {
         assert_eq!(detect_extension(&[0x4D, 0x4F, 0x43, 0x33, 0x00]), "moc3");
         assert_eq!(detect_extension(b"{\"Version\":3}"), "json");
         assert_eq!(detect_extension(&[0x00, 0x01, 0x02, 0x03]), "bin");
+        // Header cut in the middle of a multi-byte character
+        assert_eq!(detect_extension(&"{\"名\"".as_bytes()[..4]), "json");
     }
 }
@@ -0,0 +1,549 @@
+use rusqlite::{Connection, Transaction};
+use std::path::Path;
+
+/// A schema change, applied inside the migration transaction.
+type Migration = fn(&Transaction) -> rusqlite::Result<()>;
+
+/// Schema migrations in order. `PRAGMA user_version` holds how many have
+/// been applied; append new ones, never edit or reorder existing ones.
+///
+/// Databases from before versioning are at version 0 but may already have
+/// some of the early tables and columns, so those steps are idempotent.
+const MIGRATIONS: &[Migration] = &[
+    // 1: base schema
+    |tx| {
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS config (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS models (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL UNIQUE,
+                added_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE IF NOT EXISTS model_variants (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                model_path TEXT NOT NULL,
+                path TEXT NOT NULL UNIQUE,
+                name TEXT,
+                preview TEXT,
+                position INTEGER NOT NULL DEFAULT 0
+            );",
+        )
+    },
+    // 2: hash of the imported file, for skipping re-imports
+    |tx| add_column(tx, "models", "source_hash", "TEXT"),
+    // 3: content fingerprint, filled in lazily for existing rows
+    |tx| add_column(tx, "models", "asset_hash", "TEXT"),
+    // 4: per-model data out of `<field>:<model path>` config keys. CAST
+    // turns anything into a number, so only intimacy values that are
+    // numbers move; other values stay behind rather than becoming 0.
+    |tx| {
+        tx.execute_batch(
+            "CREATE TABLE model_meta (
+                model_id INTEGER PRIMARY KEY REFERENCES models(id) ON DELETE CASCADE,
+                name TEXT,
+                motions TEXT,
+                preview TEXT,
+                intimacy REAL
+            );
+            INSERT INTO model_meta (model_id, name, motions, preview, intimacy)
+            SELECT id, name, motions, preview, intimacy FROM (
+                SELECT m.id,
+                    (SELECT value FROM config WHERE key = 'name:' || m.path) AS name,
+                    (SELECT value FROM config WHERE key = 'motions:' || m.path) AS motions,
+                    (SELECT value FROM config WHERE key = 'preview:' || m.path) AS preview,
+                    (SELECT CAST(value AS REAL) FROM config
+                     WHERE key = 'intimacy:' || m.path
+                        AND CASE WHEN json_valid(value) THEN json_type(value) END
+                            IN ('integer', 'real')) AS intimacy
+                FROM models m
+            )
+            WHERE COALESCE(name, motions, preview, intimacy) IS NOT NULL;
+            DELETE FROM config WHERE substr(key, 1, 5) = 'name:'
+                OR substr(key, 1, 8) = 'motions:'
+                OR substr(key, 1, 8) = 'preview:'
+                OR (substr(key, 1, 9) = 'intimacy:'
+                    AND CASE WHEN json_valid(value) THEN json_type(value) END
+                        IN ('integer', 'real'));",
+        )
+    },
+    // 5: per-model overrides of settings; the saved position and scale
+    // belonged to the current model
+    |tx| {
+        tx.execute_batch(
+            "CREATE TABLE model_settings (
+                model_id INTEGER NOT NULL REFERENCES models(id) ON DELETE CASCADE,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (model_id, key)
+            );
+            INSERT INTO model_settings (model_id, key, value)
+            SELECT m.id, c.key, c.value
+            FROM config c
+            JOIN config cur ON cur.key = 'current_model'
+            JOIN models m ON m.path = cur.value
+            WHERE c.key IN ('model_x', 'model_y', 'model_scale');
+            DELETE FROM config WHERE key IN ('model_x', 'model_y', 'model_scale');",
+        )
+    },
+    // 6: variants keyed by model id, so they go with their model, and the
+    // selected one in model_meta instead of `variant:<model path>` keys.
+    // Variants and selections of unregistered models are dropped.
+    |tx| {
+        tx.execute_batch(
+            "CREATE TABLE model_variants_new (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                model_id INTEGER NOT NULL REFERENCES models(id) ON DELETE CASCADE,
+                path TEXT NOT NULL UNIQUE,
+                name TEXT,
+                preview TEXT,
+                position INTEGER NOT NULL DEFAULT 0
+            );
+            INSERT INTO model_variants_new (id, model_id, path, name, preview, position)
+            SELECT v.id, m.id, v.path, v.name, v.preview, v.position
+            FROM model_variants v JOIN models m ON m.path = v.model_path;
+            DROP TABLE model_variants;
+            ALTER TABLE model_variants_new RENAME TO model_variants;
+            CREATE INDEX model_variants_model_id ON model_variants (model_id);
+            ALTER TABLE model_meta ADD COLUMN active_variant TEXT;
+            INSERT INTO model_meta (model_id, active_variant)
+            SELECT m.id, c.value FROM config c JOIN models m ON c.key = 'variant:' || m.path
+            WHERE true
+            ON CONFLICT (model_id) DO UPDATE SET active_variant = excluded.active_variant;
+            DELETE FROM config WHERE substr(key, 1, 8) = 'variant:';",
+        )
+    },
+];
+
+/// Schema version this build creates and understands.
+pub const LATEST: u32 = MIGRATIONS.len() as u32;
+
+fn add_column(tx: &Transaction, table: &str, column: &str, decl: &str) -> rusqlite::Result<()> {
+    let exists = tx
+        .prepare(&format!("SELECT {} FROM {} LIMIT 0", column, table))
+        .is_ok();
+    if !exists {
+        tx.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, decl
+        ))?;
+    }
+    Ok(())
+}
+
+pub fn user_version(conn: &Connection) -> Result<u32, String> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+/// Bring the database up to `LATEST`.
+///
+/// A database that already holds data is first copied to `backup`,
+/// replacing an older backup. A database from a newer build is refused
+/// and left untouched. All pending migrations run in one transaction, so
+/// a failed migration leaves the previous version in place.
+pub fn migrate(conn: &mut Connection, backup: &Path) -> Result<(), String> {
+    let version = user_version(conn)?;
+    if version > LATEST {
+        return Err(format!(
+            "Database was created by a newer version of Rive2d (schema {}, this version supports up to {})",
+            version, LATEST
+        ));
+    }
+    if version == LATEST {
+        return Ok(());
+    }
+
+    if has_tables(conn)? {
+        // Vacuum next to the old backup and swap it in, so a failed backup
+        // does not cost the previous one
+        let tmp = backup.with_extension("bak.tmp");
+        if tmp.exists() {
+            std::fs::remove_file(&tmp).map_err(|e| e.to_string())?;
+        }
+        conn.execute("VACUUM INTO ?1", [tmp.to_string_lossy()])
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&tmp);
+                format!("Failed to back up database: {}", e)
+            })?;
+        std::fs::rename(&tmp, backup).map_err(|e| format!("Failed to back up database: {}", e))?;
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(&tx).map_err(|e| format!("Database migration {} failed: {}", i + 1, e))?;
+    }
+    tx.pragma_update(None, "user_version", LATEST)
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+fn has_tables(conn: &Connection) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table')",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_migrate_fresh_db() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backup = tmp.path().join("db.bak");
+        let mut conn = Connection::open(tmp.path().join("db")).unwrap();
+        migrate(&mut conn, &backup).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), LATEST);
+        assert!(columns(&conn, "models").contains(&"asset_hash".to_string()));
+        // Nothing worth backing up
+        assert!(!backup.exists());
+
+        // Up to date: a no-op
+        migrate(&mut conn, &backup).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), LATEST);
+    }
+
+    #[test]
+    fn test_migrate_unversioned_db() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backup = tmp.path().join("db.bak");
+        let mut conn = Connection::open(tmp.path().join("db")).unwrap();
+        // Schema as created before versioning, with one probed column
+        conn.execute_batch(
+            "CREATE TABLE config (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+            CREATE TABLE models (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL UNIQUE,
+                added_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                source_hash TEXT
+            );
+            INSERT INTO models (path, source_hash) VALUES ('/a.model3.json', 'abc');
+            INSERT INTO config VALUES ('current_model', '/a.model3.json');",
+        )
+        .unwrap();
+
+        migrate(&mut conn, &backup).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), LATEST);
+        let hash: String = conn
+            .query_row("SELECT source_hash FROM models", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(hash, "abc");
+        assert!(columns(&conn, "models").contains(&"asset_hash".to_string()));
+        assert!(!columns(&conn, "model_variants").is_empty());
+
+        // The backup is the database as it was
+        let old = Connection::open(&backup).unwrap();
+        assert_eq!(user_version(&old).unwrap(), 0);
+        assert!(!columns(&old, "models").contains(&"asset_hash".to_string()));
+    }
+
+    #[test]
+    fn test_migrate_replaces_backup() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backup = tmp.path().join("db.bak");
+        std::fs::write(&backup, b"old backup").unwrap();
+        let mut conn = Connection::open(tmp.path().join("db")).unwrap();
+        conn.execute_batch("CREATE TABLE config (key TEXT PRIMARY KEY, value TEXT NOT NULL)")
+            .unwrap();
+
+        migrate(&mut conn, &backup).unwrap();
+        let old = Connection::open(&backup).unwrap();
+        assert_eq!(user_version(&old).unwrap(), 0);
+        assert_eq!(columns(&old, "config"), ["key", "value"]);
+        assert!(!backup.with_extension("bak.tmp").exists());
+    }
+
+    #[test]
+    fn test_migrations_apply_stepwise() {
+        for from in 1..LATEST {
+            let tmp = tempfile::tempdir().unwrap();
+            let mut conn = Connection::open(tmp.path().join("db")).unwrap();
+            let tx = conn.transaction().unwrap();
+            for migration in &MIGRATIONS[..from as usize] {
+                migration(&tx).unwrap();
+            }
+            tx.pragma_update(None, "user_version", from).unwrap();
+            tx.commit().unwrap();
+
+            migrate(&mut conn, &tmp.path().join("db.bak")).unwrap();
+            assert_eq!(user_version(&conn).unwrap(), LATEST, "from {}", from);
+            assert!(tmp.path().join("db.bak").exists());
+        }
+    }
+
+    #[test]
+    fn test_migrate_model_meta() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open(tmp.path().join("db")).unwrap();
+        let tx = conn.transaction().unwrap();
+        for migration in &MIGRATIONS[..3] {
+            migration(&tx).unwrap();
+        }
+        tx.execute_batch(
+            "INSERT INTO models (path) VALUES ('/a.model3.json'), ('/b.model3.json');
+            INSERT INTO config VALUES
+                ('name:/a.model3.json', 'Alice'),
+                ('motions:/a.model3.json', '{\"Tap\":\"Idle\"}'),
+                ('intimacy:/a.model3.json', '42.5'),
+                ('preview:/gone.model3.json', '/gone.png'),
+                ('tap_motion', 'false');
+            PRAGMA user_version = 3;",
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        migrate(&mut conn, &tmp.path().join("db.bak")).unwrap();
+        let meta: (String, String, Option<String>, f64) = conn
+            .query_row(
+                "SELECT name, motions, preview, intimacy FROM model_meta
+                 JOIN models ON models.id = model_id WHERE path = '/a.model3.json'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            meta,
+            ("Alice".into(), r#"{"Tap":"Idle"}"#.into(), None, 42.5)
+        );
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM model_meta", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 1);
+        let keys: Vec<String> = conn
+            .prepare("SELECT key FROM config")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(keys, ["tap_motion"]);
+
+        // Metadata goes with its model
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+            DELETE FROM models WHERE path = '/a.model3.json';",
+        )
+        .unwrap();
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM model_meta", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 0);
+    }
+
+    #[test]
+    fn test_migrate_keeps_malformed_intimacy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open(tmp.path().join("db")).unwrap();
+        let tx = conn.transaction().unwrap();
+        for migration in &MIGRATIONS[..3] {
+            migration(&tx).unwrap();
+        }
+        tx.execute_batch(
+            "INSERT INTO models (path) VALUES ('/a.model3.json'), ('/b.model3.json');
+            INSERT INTO config VALUES
+                ('intimacy:/a.model3.json', ' 7 '),
+                ('intimacy:/b.model3.json', 'lots'),
+                ('name:/b.model3.json', 'Bob');
+            PRAGMA user_version = 3;",
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        migrate(&mut conn, &tmp.path().join("db.bak")).unwrap();
+        let meta: Vec<(String, Option<String>, Option<f64>)> = conn
+            .prepare(
+                "SELECT path, name, intimacy FROM model_meta
+                 JOIN models ON models.id = model_id ORDER BY path",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(
+            meta,
+            [
+                ("/a.model3.json".into(), None, Some(7.0)),
+                ("/b.model3.json".into(), Some("Bob".into()), None),
+            ]
+        );
+        // Not a number: left where it was instead of turning into 0
+        let left: Vec<(String, String)> = conn
+            .prepare("SELECT key, value FROM config")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(left, [("intimacy:/b.model3.json".into(), "lots".into())]);
+    }
+
+    #[test]
+    fn test_migrate_model_settings() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open(tmp.path().join("db")).unwrap();
+        let tx = conn.transaction().unwrap();
+        for migration in &MIGRATIONS[..4] {
+            migration(&tx).unwrap();
+        }
+        tx.execute_batch(
+            "INSERT INTO models (path) VALUES ('/a.model3.json'), ('/b.model3.json');
+            INSERT INTO config VALUES
+                ('current_model', '/b.model3.json'),
+                ('model_x', '120'),
+                ('model_scale', '0.5'),
+                ('tap_motion', 'false');
+            PRAGMA user_version = 4;",
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        migrate(&mut conn, &tmp.path().join("db.bak")).unwrap();
+        let rows: Vec<(String, String, String)> = conn
+            .prepare(
+                "SELECT path, key, value FROM model_settings
+                 JOIN models ON models.id = model_id ORDER BY key",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let b = "/b.model3.json".to_string();
+        assert_eq!(
+            rows,
+            [
+                (b.clone(), "model_scale".into(), "0.5".into()),
+                (b, "model_x".into(), "120".into()),
+            ]
+        );
+        let globals: Vec<String> = conn
+            .prepare("SELECT key FROM config ORDER BY key")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(globals, ["current_model", "tap_motion"]);
+    }
+
+    #[test]
+    fn test_migrate_model_variants() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open(tmp.path().join("db")).unwrap();
+        let tx = conn.transaction().unwrap();
+        for migration in &MIGRATIONS[..5] {
+            migration(&tx).unwrap();
+        }
+        tx.execute_batch(
+            "INSERT INTO models (path) VALUES ('/a/a.model3.json'), ('/b/b.model3.json');
+            INSERT INTO model_meta (model_id, name)
+                SELECT id, 'Alice' FROM models WHERE path = '/a/a.model3.json';
+            INSERT INTO model_variants (model_path, path, name, position) VALUES
+                ('/a/a.model3.json', '/a/a.model3.json', 'Default', 0),
+                ('/a/a.model3.json', '/a/summer.model3.json', 'Summer', 1),
+                ('/b/b.model3.json', '/b/b.model3.json', NULL, 0),
+                ('/b/b.model3.json', '/b/night.model3.json', NULL, 1),
+                ('/gone/gone.model3.json', '/gone/gone.model3.json', NULL, 0);
+            INSERT INTO config VALUES
+                ('variant:/a/a.model3.json', '/a/summer.model3.json'),
+                ('variant:/b/b.model3.json', '/b/night.model3.json'),
+                ('variant:/gone/gone.model3.json', '/gone/gone.model3.json'),
+                ('tap_motion', 'false');
+            PRAGMA user_version = 5;",
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        migrate(&mut conn, &tmp.path().join("db.bak")).unwrap();
+        let variants: Vec<(String, String, i64)> = conn
+            .prepare(
+                "SELECT m.path, v.path, v.position FROM model_variants v
+                 JOIN models m ON m.id = v.model_id ORDER BY v.path",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let a = "/a/a.model3.json".to_string();
+        let b = "/b/b.model3.json".to_string();
+        assert_eq!(
+            variants,
+            [
+                (a.clone(), a.clone(), 0),
+                (a.clone(), "/a/summer.model3.json".into(), 1),
+                (b.clone(), b.clone(), 0),
+                (b.clone(), "/b/night.model3.json".into(), 1),
+            ]
+        );
+        let meta: Vec<(String, Option<String>, String)> = conn
+            .prepare(
+                "SELECT path, name, active_variant FROM model_meta
+                 JOIN models ON models.id = model_id ORDER BY path",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(
+            meta,
+            [
+                (a, Some("Alice".into()), "/a/summer.model3.json".into()),
+                (b, None, "/b/night.model3.json".into()),
+            ]
+        );
+        let keys: Vec<String> = conn
+            .prepare("SELECT key FROM config")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(keys, ["tap_motion"]);
+
+        // Variants go with their model
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+            DELETE FROM models WHERE path = '/a/a.model3.json';",
+        )
+        .unwrap();
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM model_variants", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[test]
+    fn test_refuses_newer_db() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backup = tmp.path().join("db.bak");
+        let mut conn = Connection::open(tmp.path().join("db")).unwrap();
+        conn.execute_batch("CREATE TABLE future (x TEXT)").unwrap();
+        conn.pragma_update(None, "user_version", LATEST + 1)
+            .unwrap();
+
+        let err = migrate(&mut conn, &backup).unwrap_err();
+        assert!(err.contains("newer version"), "{}", err);
+        assert_eq!(user_version(&conn).unwrap(), LATEST + 1);
+        assert!(columns(&conn, "config").is_empty());
+        assert!(!backup.exists());
+    }
+}
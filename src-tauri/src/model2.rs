@@ -0,0 +1,197 @@
+use crate::model3::prune;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeMap;
+
+// Same conventions as `model3`: known fields are optional and skipped when
+// absent, unknown fields are kept in `extra`, numbers stay `Number`.
+
+/// Cubism 2 model descriptor (`.model.json`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Model2 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Path to the `.moc` file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub textures: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motions: Option<BTreeMap<String, Vec<Motion2>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expressions: Option<Vec<Expression2>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physics: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pose: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_areas: Option<Vec<HitArea2>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Motion2 {
+    /// Path to the `.mtn` file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+    /// Fade-in duration in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_in: Option<Number>,
+    /// Fade-out duration in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_out: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Expression2 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HitArea2 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Placement of the model on the canvas, in model units.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Layout {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub center_x: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub center_y: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bottom: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Model2 {
+    /// Parse a descriptor, failing on any value of the wrong type.
+    pub fn from_slice(data: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(data).map_err(|e| e.to_string())
+    }
+
+    /// Like `from_slice`, but values of the wrong type are left out rather
+    /// than failing the whole descriptor, as `Model3::from_slice_lenient`
+    /// does. Also returns the JSON pointers of the values left out.
+    pub fn from_slice_lenient(data: &[u8]) -> Result<(Self, Vec<String>), String> {
+        let mut json: Value = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+        if !json.is_object() {
+            return Err("Model descriptor is not a JSON object".to_string());
+        }
+        let mut dropped = Vec::new();
+        prune(
+            &mut json,
+            "",
+            &|v| Self::deserialize(v).is_ok(),
+            &mut dropped,
+        );
+        let model = Self::deserialize(&json).map_err(|e| e.to_string())?;
+        Ok((model, dropped))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = include_str!("../tests/fixtures/sample.model.json");
+
+    #[test]
+    fn test_roundtrip() {
+        let model = Model2::from_slice(SAMPLE.as_bytes()).unwrap();
+        let out: Value = serde_json::from_slice(&serde_json::to_vec(&model).unwrap()).unwrap();
+        assert_eq!(out, serde_json::from_str::<Value>(SAMPLE).unwrap());
+    }
+
+    #[test]
+    fn test_typed_fields() {
+        let model = Model2::from_slice(SAMPLE.as_bytes()).unwrap();
+        assert_eq!(model.model.as_deref(), Some("model.moc"));
+        assert_eq!(model.textures.as_ref().unwrap().len(), 2);
+
+        let tap = &model.motions.as_ref().unwrap()["tap_body"][0];
+        assert_eq!(tap.sound.as_deref(), Some("sounds/tap.mp3"));
+        assert_eq!(tap.fade_in.as_ref().unwrap().as_i64(), Some(300));
+
+        assert_eq!(
+            model.hit_areas.as_ref().unwrap()[0].id.as_deref(),
+            Some("D_REF.HEAD")
+        );
+        assert_eq!(
+            model.expressions.as_ref().unwrap()[0].name.as_deref(),
+            Some("f01")
+        );
+        let layout = model.layout.as_ref().unwrap();
+        assert_eq!(layout.width.as_ref().unwrap().as_f64(), Some(2.0));
+        assert!(model.extra.contains_key("init_params"));
+    }
+
+    #[test]
+    fn test_lenient_drops_only_bad_values() {
+        let src = r#"{
+            "name": 3,
+            "model": "model.moc",
+            "textures": ["a.png", null, "b.png"],
+            "motions": {"idle": [{"file": "idle.mtn", "fade_in": "500"}]},
+            "hit_areas": [{"name": "head", "id": ["D_REF.HEAD"]}]
+        }"#;
+        assert!(Model2::from_slice(src.as_bytes()).is_err());
+
+        let (model, mut dropped) = Model2::from_slice_lenient(src.as_bytes()).unwrap();
+        dropped.sort();
+        assert_eq!(
+            dropped,
+            [
+                "/hit_areas/0/id",
+                "/motions/idle/0/fade_in",
+                "/name",
+                "/textures/1",
+            ]
+        );
+        // Later entries keep their index
+        assert_eq!(model.textures.as_deref().unwrap(), ["a.png", "", "b.png"]);
+        assert_eq!(model.model.as_deref(), Some("model.moc"));
+        let idle = &model.motions.as_ref().unwrap()["idle"][0];
+        assert_eq!(idle.file.as_deref(), Some("idle.mtn"));
+        assert_eq!(idle.fade_in, None);
+
+        let (model, dropped) = Model2::from_slice_lenient(SAMPLE.as_bytes()).unwrap();
+        assert!(dropped.is_empty());
+        assert_eq!(model, Model2::from_slice(SAMPLE.as_bytes()).unwrap());
+        assert!(Model2::from_slice_lenient(b"[1]").is_err());
+    }
+}
@@ -0,0 +1,908 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeMap;
+
+// Every struct keeps fields it doesn't know about in `extra`, and every known
+// field is optional and skipped when absent, so `from_slice` → `to_vec_pretty`
+// preserves the document (apart from key order).
+//
+// Numbers are kept as `serde_json::Number`: LPK assets mix `500` and `500.0`
+// for the same field, and an exact round-trip needs the original form.
+
+/// Deserialize a present field (including `null`) as `Some`, so nullable
+/// fields like `"Bubble": null` survive a round-trip.
+fn present<'de, T, D>(d: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(d).map(Some)
+}
+
+/// Cubism 3/4 model descriptor (`.model3.json`), including Live2DViewerEX
+/// extensions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Model3 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<Number>,
+    #[serde(rename = "Type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_references: Option<FileReferences>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_areas: Option<Vec<HitArea>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub controllers: Option<Controllers>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<Options>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<Group>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<Bounds>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub bubble: Option<Value>,
+
+    // Legacy top-level fields (moved into Controllers/Options in newer files)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_params: Option<Vec<ParamHitItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loop_params: Option<Vec<ParamLoopItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intimacy_param: Option<IntimacySystem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lip_sync: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eye_blink: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_motion: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale_factor: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tex_fixed: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lip_scale: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aniso_level: Option<Number>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FileReferences {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moc: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub textures: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motions: Option<BTreeMap<String, Vec<Motion>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physics: Option<String>,
+    #[serde(rename = "PhysicsV2", default, skip_serializing_if = "Option::is_none")]
+    pub physics_v2: Option<PhysicsV2>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expressions: Option<Vec<Expression>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pose: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PhysicsV2 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_weight: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Expression {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// ---------------------------------------------------------------------------
+// Motions
+// ---------------------------------------------------------------------------
+
+/// One entry of a `FileReferences.Motions` group.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Motion {
+    // Core
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    // Animation control
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_loop: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrap_mode: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_in: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_out: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion_duration: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interruptable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignorable: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub time_limit: Option<Option<Number>>,
+
+    // Sound
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound_delay: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound_volume: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound_channel: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound_loop: Option<bool>,
+
+    // Text / dialogue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_delay: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_duration: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
+    // Chaining
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_mtn: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pre_mtn: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_command: Option<String>,
+
+    // Interaction / state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<Choice>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub var_floats: Option<Vec<VarFloat>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intimacy: Option<Intimacy>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Choice {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_mtn: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct VarFloat {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `1` = condition, `2` = action
+    #[serde(rename = "Type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Intimacy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equal: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bonus: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// ---------------------------------------------------------------------------
+// HitAreas, Groups, Bounds, Options
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HitArea {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Cubism standard parameter group (`Groups[]`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Group {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub axes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub factors: Option<Vec<Number>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Bounds {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub center_x: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub center_y: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Options {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale_factor: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position_x: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position_y: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tex_fixed: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tex_type: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aniso_level: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mask_buffer_size: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_mod: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// ---------------------------------------------------------------------------
+// Controllers
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Controllers {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub param_hit: Option<ItemsController<ParamHitItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub param_loop: Option<ItemsController<ParamLoopItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_trigger: Option<ItemsController<KeyTriggerItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eye_blink: Option<EyeBlink>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lip_sync: Option<LipSync>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mouse_tracking: Option<MouseTracking>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub param_value: Option<ItemsController<ParamValueItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub param_trigger: Option<ItemsController<ParamTriggerItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part_opacity: Option<ItemsController<OpacityItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artmesh_opacity: Option<ItemsController<OpacityItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intimacy_system: Option<IntimacySystem>,
+
+    // Simple toggles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_breath: Option<Toggle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_motion: Option<Toggle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accelerometer: Option<Toggle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub face_tracking: Option<Toggle>,
+
+    // Placeholders (always empty objects in practice)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub microphone: Option<Toggle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Toggle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub area_trigger: Option<Toggle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hand_trigger: Option<Toggle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hand_tracking: Option<Toggle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artmesh_color: Option<Toggle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artmesh_culling: Option<Toggle>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Controller with only an `Enabled` flag.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Toggle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Controller with `Enabled` and a list of `Items`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemsController<T> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<T>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl<T> Default for ItemsController<T> {
+    fn default() -> Self {
+        Self {
+            enabled: None,
+            items: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ParamHitItem {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_area: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub axis: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub factor: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_type: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_param: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_mtn: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_mtn: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_mtn: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub begin_mtn: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low_priority: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<Number>,
+    #[serde(rename = "Type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ParamLoopItem {
+    /// Single parameter ID (legacy format)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(rename = "Type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blend_mode: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct KeyTriggerItem {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub down_mtn: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Parameter mapping used by EyeBlink, LipSync and MouseTracking items.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ParamMapping {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub axis: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blend_mode: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inverted: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EyeBlink {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_interval: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_interval: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<ParamMapping>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LipSync {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gain: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smooth_time: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<ParamMapping>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MouseTracking {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smooth_time: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<ParamMapping>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ParamValueItem {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_values: Option<Vec<KeyValue>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hidden: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct KeyValue {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ParamTriggerItem {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<ParamThreshold>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ParamThreshold {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Item of PartOpacity / ArtmeshOpacity.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OpacityItem {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hidden: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct IntimacySystem {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_value: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_value: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_value: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bonus_active: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bonus_inactive: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bonus_limit: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// ---------------------------------------------------------------------------
+// Reading / writing
+// ---------------------------------------------------------------------------
+
+// Files are read through `ModelDescriptor::load`, which picks the format,
+// so only byte-level reading and writing live here.
+
+impl Model3 {
+    /// Parse a descriptor, failing on any value of the wrong type.
+    pub fn from_slice(data: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(data).map_err(|e| e.to_string())
+    }
+
+    /// Like `from_slice`, but values that don't fit the model (a `null`
+    /// texture, a bool stored as a number, ...) are left out rather than
+    /// failing the whole descriptor. Also returns the JSON pointers of the
+    /// values left out.
+    pub fn from_slice_lenient(data: &[u8]) -> Result<(Self, Vec<String>), String> {
+        let mut json: Value = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+        if !json.is_object() {
+            return Err("Model descriptor is not a JSON object".to_string());
+        }
+        let mut dropped = Vec::new();
+        prune(
+            &mut json,
+            "",
+            &|v| Self::deserialize(v).is_ok(),
+            &mut dropped,
+        );
+        let model = Self::deserialize(&json).map_err(|e| e.to_string())?;
+        Ok((model, dropped))
+    }
+
+    /// Serialize for writing back to disk.
+    pub fn to_vec_pretty(&self) -> Result<Vec<u8>, String> {
+        serde_json::to_vec_pretty(self).map_err(|e| e.to_string())
+    }
+
+    /// Texture paths, empty when absent.
+    pub fn textures(&self) -> &[String] {
+        self.file_references
+            .as_ref()
+            .and_then(|fr| fr.textures.as_deref())
+            .unwrap_or(&[])
+    }
+
+    /// Motion groups by name.
+    pub fn motions(&self) -> Option<&BTreeMap<String, Vec<Motion>>> {
+        self.file_references.as_ref()?.motions.as_ref()
+    }
+
+    pub fn hit_areas(&self) -> &[HitArea] {
+        self.hit_areas.as_deref().unwrap_or(&[])
+    }
+}
+
+/// Remove the parts of `value` that make `fits` fail, recording their
+/// pointers in `dropped`. Shared with `model2`, whose model follows the
+/// same conventions.
+///
+/// Every field of the model is optional and every array entry independent,
+/// so each child can be checked on its own, wrapped in its parent. Array
+/// entries are blanked (to `{}` or `""`) when that fits, so the pointers of
+/// later entries stay valid.
+pub(crate) fn prune(
+    value: &mut Value,
+    pointer: &str,
+    fits: &dyn Fn(&Value) -> bool,
+    dropped: &mut Vec<String>,
+) {
+    if fits(value) {
+        return;
+    }
+    match value {
+        Value::Object(map) => {
+            let keys: Vec<String> = map.keys().cloned().collect();
+            for key in keys {
+                let wrap =
+                    |v: &Value| fits(&Value::Object(Map::from_iter([(key.clone(), v.clone())])));
+                let child_pointer =
+                    format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+                let before = dropped.len();
+                let child = map.get_mut(&key).unwrap();
+                prune(child, &child_pointer, &wrap, dropped);
+                if !wrap(child) {
+                    map.remove(&key);
+                    dropped.truncate(before);
+                    dropped.push(child_pointer);
+                }
+            }
+        }
+        Value::Array(items) => {
+            let mut keep = Vec::with_capacity(items.len());
+            for (i, item) in items.iter_mut().enumerate() {
+                let wrap = |v: &Value| fits(&Value::Array(vec![v.clone()]));
+                let child_pointer = format!("{}/{}", pointer, i);
+                let before = dropped.len();
+                prune(item, &child_pointer, &wrap, dropped);
+                let fitted = wrap(item);
+                if !fitted {
+                    dropped.truncate(before);
+                    dropped.push(child_pointer);
+                    if let Some(blank) = [Value::Object(Map::new()), Value::String(String::new())]
+                        .into_iter()
+                        .find(|b| wrap(b))
+                    {
+                        *item = blank;
+                        keep.push(true);
+                        continue;
+                    }
+                }
+                keep.push(fitted);
+            }
+            let mut keep = keep.into_iter();
+            items.retain(|_| keep.next().unwrap());
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL: &str = include_str!("../tests/fixtures/full.model3.json");
+    const LEGACY: &str = include_str!("../tests/fixtures/legacy.model3.json");
+
+    fn roundtrip(src: &str) {
+        let model = Model3::from_slice(src.as_bytes()).unwrap();
+        let out: Value = serde_json::from_slice(&model.to_vec_pretty().unwrap()).unwrap();
+        let orig: Value = serde_json::from_str(src).unwrap();
+        assert_eq!(out, orig);
+    }
+
+    #[test]
+    fn test_roundtrip_full() {
+        roundtrip(FULL);
+    }
+
+    #[test]
+    fn test_roundtrip_legacy() {
+        roundtrip(LEGACY);
+    }
+
+    #[test]
+    fn test_typed_fields() {
+        let model = Model3::from_slice(FULL.as_bytes()).unwrap();
+        assert_eq!(model.version.as_ref().and_then(|v| v.as_u64()), Some(3));
+        assert_eq!(model.textures(), ["texture_00.png", "texture_01.png"]);
+
+        let motions = model.motions().unwrap();
+        let tap = &motions["Tap身体"][0];
+        assert_eq!(tap.sound.as_deref(), Some("voice.wav"));
+        assert_eq!(tap.next_mtn.as_deref(), Some("Idle:loop"));
+        assert_eq!(tap.pre_mtn, Some(None));
+        assert_eq!(
+            tap.var_floats.as_ref().unwrap()[0].code.as_deref(),
+            Some("equal 1")
+        );
+        assert_eq!(
+            tap.intimacy
+                .as_ref()
+                .unwrap()
+                .bonus
+                .as_ref()
+                .unwrap()
+                .as_i64(),
+            Some(5)
+        );
+        assert_eq!(motions["Menu"][0].choices.as_ref().unwrap().len(), 2);
+
+        assert_eq!(model.hit_areas()[0].motion.as_deref(), Some("Tap身体"));
+        let controllers = model.controllers.as_ref().unwrap();
+        let hit = &controllers
+            .param_hit
+            .as_ref()
+            .unwrap()
+            .items
+            .as_ref()
+            .unwrap()[0];
+        assert_eq!(hit.max_mtn.as_deref(), Some("Tap身体"));
+        assert_eq!(hit.kind.as_ref().unwrap().as_i64(), Some(2));
+        assert!(controllers.microphone.is_some());
+        assert_eq!(
+            model.options.as_ref().unwrap().name.as_deref(),
+            Some("sample")
+        );
+        assert_eq!(model.bubble, Some(Value::Null));
+    }
+
+    #[test]
+    fn test_unknown_fields_kept() {
+        let model = Model3::from_slice(FULL.as_bytes()).unwrap();
+        assert!(model.extra.contains_key("CustomTopLevel"));
+        let idle = &model.motions().unwrap()["Idle"][0];
+        assert_eq!(idle.extra["VendorField"], serde_json::json!({"a": 1}));
+    }
+
+    #[test]
+    fn test_lenient_drops_only_bad_values() {
+        let src = r#"{
+            "Version": 3,
+            "FileReferences": {
+                "Moc": "a.moc3",
+                "Textures": ["a.png", null, "b.png"],
+                "Physics": 7,
+                "Motions": {"Idle": [{"File": "idle.motion3.json", "FadeIn": true}]}
+            },
+            "LipSync": 1,
+            "EyeBlink": true,
+            "HitAreas": "none"
+        }"#;
+        assert!(Model3::from_slice(src.as_bytes()).is_err());
+
+        let (model, mut dropped) = Model3::from_slice_lenient(src.as_bytes()).unwrap();
+        dropped.sort();
+        assert_eq!(
+            dropped,
+            [
+                "/FileReferences/Motions/Idle/0/FadeIn",
+                "/FileReferences/Physics",
+                "/FileReferences/Textures/1",
+                "/HitAreas",
+                "/LipSync",
+            ]
+        );
+        // Later entries keep their index
+        assert_eq!(model.textures(), ["a.png", "", "b.png"]);
+        let fr = model.file_references.as_ref().unwrap();
+        assert_eq!(fr.moc.as_deref(), Some("a.moc3"));
+        assert_eq!(fr.physics, None);
+        let idle = &model.motions().unwrap()["Idle"][0];
+        assert_eq!(idle.file.as_deref(), Some("idle.motion3.json"));
+        assert_eq!(model.eye_blink, Some(true));
+        assert_eq!(model.lip_sync, None);
+
+        let (model, dropped) = Model3::from_slice_lenient(FULL.as_bytes()).unwrap();
+        assert!(dropped.is_empty());
+        assert_eq!(model, Model3::from_slice(FULL.as_bytes()).unwrap());
+        assert!(Model3::from_slice_lenient(b"[1]").is_err());
+    }
+
+    #[test]
+    fn test_legacy_fields() {
+        let model = Model3::from_slice(LEGACY.as_bytes()).unwrap();
+        assert_eq!(model.lip_sync, Some(true));
+        assert_eq!(
+            model.hit_params.as_ref().unwrap()[0].hit_area.as_deref(),
+            Some("TouchDrag1")
+        );
+        assert_eq!(
+            model.loop_params.as_ref().unwrap()[0].id.as_deref(),
+            Some("Param4")
+        );
+        assert!(model.groups.is_none());
+    }
+}
@@ -0,0 +1,478 @@
+use crate::lpk;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+/// Archive entry carrying Rive2d's own model settings in exported packages.
+pub const METADATA_NAME: &str = "rive2d.json";
+
+/// Layout of an exported LPK.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum PackFormat {
+    /// Files under their own names, no manifest
+    #[serde(rename = "plain")]
+    Plain,
+    /// Live2DViewerEX STD_2_0: a manifest plus encrypted, hash-named entries
+    #[serde(rename = "STD_2_0")]
+    Std2,
+}
+
+/// Settings stored in the `METADATA_NAME` entry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PackMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Hit area → motion group mappings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motions: Option<Value>,
+    /// Archive entry of the preview image (plain packages only; STD_2_0
+    /// packages use the manifest avatar)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+}
+
+/// A model descriptor to pack.
+#[derive(Debug, Clone)]
+pub struct PackCostume {
+    pub path: PathBuf,
+    pub name: Option<String>,
+}
+
+/// A file going into the archive, keyed by its path relative to the
+/// package root.
+struct Asset {
+    rel: String,
+    path: PathBuf,
+}
+
+/// Write `costumes` (the default first) and every file their descriptors
+/// reference to an LPK at `out_path`.
+///
+/// The package root is the directory of the first costume; files outside of
+/// it are not packed. `preview` is stored as the package avatar.
+pub fn pack_lpk(
+    out_path: &Path,
+    costumes: &[PackCostume],
+    format: PackFormat,
+    metadata: &PackMetadata,
+    preview: Option<&Path>,
+) -> Result<(), String> {
+    let first = costumes.first().ok_or("Nothing to pack")?;
+    let root = first
+        .path
+        .parent()
+        .ok_or("Invalid model path")?
+        .canonicalize()
+        .map_err(|e| e.to_string())?;
+
+    // Descriptors with the referenced file of each JSON string they contain
+    let mut descriptors = Vec::new();
+    let mut assets: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for costume in costumes {
+        let path = costume.path.canonicalize().map_err(|e| e.to_string())?;
+        let rel = relative(&root, &path)
+            .ok_or_else(|| format!("{} is outside the model directory", path.display()))?;
+        let data = std::fs::read(&path).map_err(|e| e.to_string())?;
+        let json: Value = serde_json::from_slice(&data)
+            .map_err(|e| format!("Invalid model descriptor {}: {}", rel, e))?;
+
+        let mut refs = BTreeMap::new();
+        collect_refs(&json, path.parent().unwrap_or(&root), &root, &mut refs);
+        assets.extend(refs.values().map(|a| (a.rel.clone(), a.path.clone())));
+        descriptors.push(Descriptor {
+            rel,
+            name: costume.name.as_deref(),
+            data,
+            json,
+            refs,
+        });
+    }
+    for descriptor in &descriptors {
+        assets.remove(&descriptor.rel);
+    }
+
+    let file = std::fs::File::create(out_path).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(file);
+    let result = match format {
+        PackFormat::Plain => write_plain(&mut zip, &descriptors, &assets, metadata, preview),
+        PackFormat::Std2 => write_std2(&mut zip, &descriptors, &assets, metadata, preview),
+    }
+    .and_then(|_| zip.finish().map(|_| ()).map_err(|e| e.to_string()));
+    if result.is_err() {
+        std::fs::remove_file(out_path).ok();
+    }
+    result
+}
+
+/// A costume descriptor as read from disk.
+struct Descriptor<'a> {
+    rel: String,
+    name: Option<&'a str>,
+    data: Vec<u8>,
+    json: Value,
+    /// Referenced files, keyed by the string used in the descriptor
+    refs: BTreeMap<String, Asset>,
+}
+
+fn write_plain(
+    zip: &mut zip::ZipWriter<std::fs::File>,
+    descriptors: &[Descriptor],
+    assets: &BTreeMap<String, PathBuf>,
+    metadata: &PackMetadata,
+    preview: Option<&Path>,
+) -> Result<(), String> {
+    let options = zip::write::SimpleFileOptions::default();
+    for descriptor in descriptors {
+        write_bytes(zip, &descriptor.rel, &descriptor.data, options)?;
+    }
+    for (rel, path) in assets {
+        zip.start_file(rel.as_str(), options)
+            .map_err(|e| e.to_string())?;
+        copy_file(zip, path, None)?;
+    }
+
+    let mut metadata = metadata.clone();
+    if let Some(preview) = preview {
+        let mut name = preview_name(preview);
+        if assets.contains_key(&name) || descriptors.iter().any(|d| d.rel == name) {
+            name = format!("rive2d_{}", name);
+        }
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| e.to_string())?;
+        copy_file(zip, preview, None)?;
+        metadata.preview = Some(name);
+    }
+    write_metadata(zip, &metadata, options)
+}
+
+fn write_std2(
+    zip: &mut zip::ZipWriter<std::fs::File>,
+    descriptors: &[Descriptor],
+    assets: &BTreeMap<String, PathBuf>,
+    metadata: &PackMetadata,
+    preview: Option<&Path>,
+) -> Result<(), String> {
+    // Encrypted entries are noise to the compressor
+    let options =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let id = format!("{:x}", md5::compute(&descriptors[0].data));
+    let key = |entry: &str| lpk::java_hash_code(&format!("{}{}", id, entry));
+
+    for (rel, path) in assets {
+        let entry = hashed_name(rel, "bin");
+        zip.start_file(entry.as_str(), options)
+            .map_err(|e| e.to_string())?;
+        copy_file(zip, path, Some(key(&entry)))?;
+    }
+
+    let mut costumes = Vec::new();
+    for descriptor in descriptors {
+        // Point file references at the hashed entries
+        let mut json = descriptor.json.clone();
+        rewrite_refs(&mut json, &|s| {
+            descriptor
+                .refs
+                .get(s)
+                .map(|asset| hashed_name(&asset.rel, "bin"))
+        });
+        let data = serde_json::to_vec(&json).map_err(|e| e.to_string())?;
+        let entry = hashed_name(&descriptor.rel, "bin3");
+        write_bytes(
+            zip,
+            &entry,
+            &lpk::decrypt_lcg_xor(&data, key(&entry)),
+            options,
+        )?;
+        costumes.push(serde_json::json!({
+            "name": descriptor.name.unwrap_or(""),
+            "path": entry,
+        }));
+    }
+
+    let avatar = match preview {
+        Some(preview) => {
+            let entry = hashed_name(&preview_name(preview), "bin");
+            zip.start_file(entry.as_str(), options)
+                .map_err(|e| e.to_string())?;
+            copy_file(zip, preview, Some(key(&entry)))?;
+            entry
+        }
+        None => String::new(),
+    };
+
+    let manifest = serde_json::json!({
+        "type": "STD_2_0",
+        "encrypt": "true",
+        "id": id,
+        "name": metadata.name.as_deref().unwrap_or("model"),
+        "list": [{ "avatar": avatar, "costume": costumes }],
+    });
+    let data = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    write_bytes(zip, lpk::MANIFEST_NAME, &data, options)?;
+    write_metadata(zip, metadata, options)
+}
+
+fn write_metadata(
+    zip: &mut zip::ZipWriter<std::fs::File>,
+    metadata: &PackMetadata,
+    options: zip::write::SimpleFileOptions,
+) -> Result<(), String> {
+    let data = serde_json::to_vec_pretty(metadata).map_err(|e| e.to_string())?;
+    write_bytes(zip, METADATA_NAME, &data, options)
+}
+
+fn write_bytes(
+    zip: &mut zip::ZipWriter<std::fs::File>,
+    name: &str,
+    data: &[u8],
+    options: zip::write::SimpleFileOptions,
+) -> Result<(), String> {
+    zip.start_file(name, options).map_err(|e| e.to_string())?;
+    zip.write_all(data).map_err(|e| e.to_string())
+}
+
+/// Stream `path` into the current entry, encrypting it with `key` if given.
+fn copy_file(
+    zip: &mut zip::ZipWriter<std::fs::File>,
+    path: &Path,
+    key: Option<i64>,
+) -> Result<(), String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut reader: Box<dyn std::io::Read> = match key {
+        Some(key) => Box::new(lpk::DecryptReader::new(file, key)),
+        None => Box::new(file),
+    };
+    std::io::copy(&mut reader, zip)
+        .map(|_| ())
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Record every string in `json` that names a file inside `root`, resolved
+/// against the descriptor directory `dir`.
+fn collect_refs(json: &Value, dir: &Path, root: &Path, refs: &mut BTreeMap<String, Asset>) {
+    match json {
+        Value::String(s) if !s.is_empty() && !refs.contains_key(s) => {
+            let Ok(path) = dir.join(s).canonicalize() else {
+                return;
+            };
+            if !path.is_file() {
+                return;
+            }
+            if let Some(rel) = relative(root, &path) {
+                refs.insert(s.clone(), Asset { rel, path });
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_refs(item, dir, root, refs);
+            }
+        }
+        Value::Object(map) => {
+            for value in map.values() {
+                collect_refs(value, dir, root, refs);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn rewrite_refs(json: &mut Value, map: &dyn Fn(&str) -> Option<String>) {
+    match json {
+        Value::String(s) => {
+            if let Some(new) = map(s) {
+                *s = new;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| rewrite_refs(v, map)),
+        Value::Object(object) => object.values_mut().for_each(|v| rewrite_refs(v, map)),
+        _ => {}
+    }
+}
+
+/// `path` relative to `root` with `/` separators, if it is inside `root`.
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    let parts: Vec<_> = rel
+        .components()
+        .map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Live2DViewerEX-style entry name: MD5 of the original path plus `ext`.
+fn hashed_name(rel: &str, ext: &str) -> String {
+    format!("{:x}.{}", md5::compute(rel.as_bytes()), ext)
+}
+
+fn preview_name(preview: &Path) -> String {
+    match preview.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("preview.{}", ext),
+        None => "preview".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::ModelDescriptor;
+    use crate::lpk::{extract_lpk, ExtractLimits, StmConfigSources};
+
+    const PNG: &[u8] = &[0x89, 0x50, 0x4E, 0x47, 1, 2, 3];
+    const MOC: &[u8] = b"MOC3\x00\x01\x02";
+    const MOTION: &str = r#"{"Version":3,"Meta":{},"Curves":[]}"#;
+
+    fn setup() -> (tempfile::TempDir, PathBuf) {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("model");
+        std::fs::create_dir_all(dir.join("tex")).unwrap();
+        std::fs::create_dir_all(dir.join("motions")).unwrap();
+        std::fs::write(dir.join("a.moc3"), MOC).unwrap();
+        std::fs::write(dir.join("tex/t0.png"), PNG).unwrap();
+        std::fs::write(dir.join("motions/idle.motion3.json"), MOTION).unwrap();
+        std::fs::write(dir.join("unrelated.txt"), "not referenced").unwrap();
+        std::fs::write(
+            dir.join("a.model3.json"),
+            r#"{"Version":3,"FileReferences":{"Moc":"a.moc3","Textures":["tex/t0.png"],
+                "Motions":{"Idle":[{"File":"motions/idle.motion3.json"}]}}}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("b.model3.json"),
+            r#"{"Version":3,"FileReferences":{"Moc":"a.moc3","Textures":["tex/t0.png"]}}"#,
+        )
+        .unwrap();
+        std::fs::write(tmp.path().join("cover.png"), PNG).unwrap();
+        (tmp, dir)
+    }
+
+    fn metadata() -> PackMetadata {
+        PackMetadata {
+            name: Some("Exported".to_string()),
+            motions: Some(serde_json::json!({ "Body": "Idle" })),
+            preview: None,
+        }
+    }
+
+    fn read_metadata(dir: &Path) -> PackMetadata {
+        serde_json::from_slice(&std::fs::read(dir.join(METADATA_NAME)).unwrap()).unwrap()
+    }
+
+    /// Assert that the model at `extracted` references the same asset bytes.
+    fn assert_same_assets(extracted: &Path) {
+        let desc = ModelDescriptor::load(extracted).unwrap();
+        let dir = extracted.parent().unwrap();
+        assert_eq!(std::fs::read(dir.join(desc.moc.unwrap())).unwrap(), MOC);
+        assert_eq!(std::fs::read(dir.join(&desc.textures[0])).unwrap(), PNG);
+        if let Some(idle) = desc.motions.get("Idle") {
+            let file = idle[0].file.as_deref().unwrap();
+            assert_eq!(std::fs::read_to_string(dir.join(file)).unwrap(), MOTION);
+        }
+    }
+
+    #[test]
+    fn test_plain_roundtrip() {
+        let (tmp, dir) = setup();
+        let out = tmp.path().join("out.lpk");
+        let costumes = [PackCostume {
+            path: dir.join("a.model3.json"),
+            name: None,
+        }];
+        let cover = tmp.path().join("cover.png");
+        pack_lpk(
+            &out,
+            &costumes,
+            PackFormat::Plain,
+            &metadata(),
+            Some(&cover),
+        )
+        .unwrap();
+
+        let dest = tmp.path().join("extracted");
+        let extracted = extract_lpk(
+            &dest,
+            out.to_str().unwrap(),
+            &ExtractLimits::default(),
+            &StmConfigSources::default(),
+        )
+        .unwrap();
+        assert_eq!(Path::new(&extracted[0][0].path), dest.join("a.model3.json"));
+        assert_eq!(
+            std::fs::read(dest.join("a.model3.json")).unwrap(),
+            std::fs::read(dir.join("a.model3.json")).unwrap()
+        );
+        assert_same_assets(&dest.join("a.model3.json"));
+        assert!(!dest.join("unrelated.txt").exists());
+
+        let packed = read_metadata(&dest);
+        assert_eq!(packed.name.as_deref(), Some("Exported"));
+        assert_eq!(packed.motions, metadata().motions);
+        assert_eq!(packed.preview.as_deref(), Some("preview.png"));
+        assert_eq!(std::fs::read(dest.join("preview.png")).unwrap(), PNG);
+    }
+
+    #[test]
+    fn test_std2_roundtrip() {
+        let (tmp, dir) = setup();
+        let out = tmp.path().join("out.lpk");
+        let costumes = [
+            PackCostume {
+                path: dir.join("a.model3.json"),
+                name: Some("Default".to_string()),
+            },
+            PackCostume {
+                path: dir.join("b.model3.json"),
+                name: Some("Other".to_string()),
+            },
+        ];
+        let cover = tmp.path().join("cover.png");
+        pack_lpk(&out, &costumes, PackFormat::Std2, &metadata(), Some(&cover)).unwrap();
+
+        let info = lpk::inspect_lpk(out.to_str().unwrap(), &StmConfigSources::default()).unwrap();
+        assert_eq!(info.format, "STD_2_0");
+        assert!(info.encrypted);
+
+        let dest = tmp.path().join("extracted");
+        let mut extracted = extract_lpk(
+            &dest,
+            out.to_str().unwrap(),
+            &ExtractLimits::default(),
+            &StmConfigSources::default(),
+        )
+        .unwrap();
+        assert_eq!(extracted.len(), 1);
+        let extracted = extracted.remove(0);
+        let names: Vec<_> = extracted.iter().map(|c| c.name.as_deref()).collect();
+        assert_eq!(names, [Some("Default"), Some("Other")]);
+        for costume in &extracted {
+            assert_same_assets(Path::new(&costume.path));
+        }
+        let avatar = extracted[0].avatar.as_deref().unwrap();
+        assert_eq!(std::fs::read(avatar).unwrap(), PNG);
+        assert_eq!(read_metadata(&dest).motions, metadata().motions);
+    }
+
+    #[test]
+    fn test_pack_rejects_costume_outside_root() {
+        let (tmp, dir) = setup();
+        std::fs::write(tmp.path().join("c.model3.json"), r#"{"Version":3}"#).unwrap();
+        let out = tmp.path().join("out.lpk");
+        let costumes = [
+            PackCostume {
+                path: dir.join("a.model3.json"),
+                name: None,
+            },
+            PackCostume {
+                path: tmp.path().join("c.model3.json"),
+                name: None,
+            },
+        ];
+        let err = pack_lpk(&out, &costumes, PackFormat::Plain, &metadata(), None).unwrap_err();
+        assert!(err.contains("outside the model directory"), "{}", err);
+        assert!(!out.exists());
+    }
+}
@@ -0,0 +1,654 @@
+use crate::{cache, config, fixups, repair};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::http::header::{HeaderMap, HeaderName, IF_MODIFIED_SINCE, IF_NONE_MATCH, RANGE};
+use tauri::Manager;
+
+type Response = tauri::http::Response<Vec<u8>>;
+
+/// Why a `model://` request could not be mapped to a servable file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The path is inside an allowed root but does not exist.
+    NotFound,
+    /// The path escapes every allowed root (or is not absolute / uses `..`).
+    Forbidden,
+}
+
+/// Serve model files from the filesystem via the `model://` protocol.
+///
+/// Only files under the app data `models` directory (including copies of
+/// custom previews) or the directories of registered models are served.
+pub fn handle(app: &tauri::AppHandle, request: &tauri::http::Request<Vec<u8>>) -> Response {
+    let uri = request.uri().to_string();
+    let path = uri_to_path(&uri);
+    let library = config::model_paths(app).map(|models| {
+        let roots = allowed_roots(app, &models);
+        (models, roots)
+    });
+    let (models, roots) = match library {
+        Ok(library) => library,
+        Err(e) => {
+            eprintln!("[rive2d] model:// failed to read library: {}", e);
+            return status(500, "Internal error");
+        }
+    };
+
+    let file_path = match resolve(&path, &roots) {
+        Ok(p) => p,
+        Err(ResolveError::NotFound) => return status(404, "Not found"),
+        Err(ResolveError::Forbidden) => {
+            eprintln!("[rive2d] model:// denied: {}", path);
+            return status(403, "Forbidden");
+        }
+    };
+
+    let mime = mime_type(&file_path);
+
+    // JSON descriptors and motions may be patched by fixups, so they are read
+    // whole up front; everything else is streamed from disk by `serve_file`.
+    let patched = if mime == "application/json" {
+        match load_patched(app, &file_path, &models) {
+            Ok(Some(data)) => Some(data),
+            Ok(None) => return status(404, "Not found"),
+            Err(e) => {
+                eprintln!("[rive2d] model:// failed to patch {}: {}", path, e);
+                return status(500, "Internal error");
+            }
+        }
+    } else {
+        None
+    };
+
+    serve_file(request.headers(), &file_path, mime, patched)
+}
+
+/// Patched body of a JSON asset, taken from the asset cache while the file
+/// is unchanged on disk. `None` if the file can't be read.
+fn load_patched(
+    app: &tauri::AppHandle,
+    file_path: &Path,
+    models: &[String],
+) -> Result<Option<Vec<u8>>, String> {
+    let Ok(metadata) = std::fs::metadata(file_path) else {
+        return Ok(None);
+    };
+    let stamp = cache::Stamp::of(&metadata);
+    let cache = app.state::<cache::AssetCache>();
+    if let Some(data) = cache.get(file_path, stamp) {
+        return Ok(Some(data));
+    }
+    let Ok(data) = std::fs::read(file_path) else {
+        return Ok(None);
+    };
+    let data = apply_fixups(app, file_path, models, data)?;
+    cache.insert(file_path, stamp, data.clone());
+    Ok(Some(data))
+}
+
+/// Run the enabled fixups over a JSON asset and log which ones fired for
+/// the model that owns it. Fixups already written to a repaired file are
+/// skipped for it.
+fn apply_fixups(
+    app: &tauri::AppHandle,
+    file_path: &Path,
+    models: &[String],
+    data: Vec<u8>,
+) -> Result<Vec<u8>, String> {
+    let model = owning_model(file_path, models);
+    let registry = app.state::<fixups::Registry>();
+    let mut flags = config::get_settings_with_prefix(app, fixups::SETTING_PREFIX)?;
+    let key = format!("{}{}", repair::SETTING_PREFIX, file_path.to_string_lossy());
+    if let Some(repaired) = config::get_setting(app, &key)? {
+        repair::skip_repaired(&mut flags, &repaired);
+    }
+    let (data, applied) = registry.run(file_path, data, &flags);
+    if let Some(model) = model.filter(|_| !applied.is_empty()) {
+        app.state::<fixups::FixupLog>()
+            .record(model, &file_path.to_string_lossy(), &applied);
+    }
+    Ok(data)
+}
+
+/// The registered model whose directory most closely contains `file_path`.
+fn owning_model<'a>(file_path: &Path, models: &'a [String]) -> Option<&'a str> {
+    models
+        .iter()
+        .filter_map(|m| {
+            let dir = Path::new(m).parent()?.canonicalize().ok()?;
+            file_path
+                .starts_with(&dir)
+                .then(|| (dir.components().count(), m.as_str()))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, m)| m)
+}
+
+fn status(code: u16, msg: &str) -> Response {
+    tauri::http::Response::builder()
+        .status(code)
+        .body(msg.as_bytes().to_vec())
+        .unwrap()
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => "application/json",
+        Some("moc3") | Some("moc") => "application/octet-stream",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("wav") => "audio/wav",
+        Some("ogg") => "audio/ogg",
+        Some("mp3") => "audio/mpeg",
+        Some("mtn") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Range / conditional requests
+// ---------------------------------------------------------------------------
+
+/// Inclusive byte range within a response body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// Outcome of interpreting a `Range` header against a body of known length.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// No usable range: send the whole body with 200.
+    Full,
+    /// Send the given slice with 206.
+    Partial(ByteRange),
+    /// The range lies outside the body: answer 416.
+    Unsatisfiable,
+}
+
+/// Build the response for `file_path`, honouring `Range`, `If-None-Match`
+/// and `If-Modified-Since`.
+///
+/// `body` overrides the file contents (used for patched JSON); otherwise the
+/// file is read from disk, and only the requested slice for range requests.
+pub fn serve_file(
+    headers: &HeaderMap,
+    file_path: &Path,
+    mime: &str,
+    body: Option<Vec<u8>>,
+) -> Response {
+    let Ok(meta) = std::fs::metadata(file_path) else {
+        return status(404, "Not found");
+    };
+    // A patched body changes with the enabled fixups while the file stays
+    // the same, so it is validated by its content alone
+    let (tag, modified) = match &body {
+        Some(b) => (format!("\"{:x}\"", md5::compute(b)), None),
+        None => (etag(meta.len(), meta.modified().ok()), meta.modified().ok()),
+    };
+
+    let mut builder = tauri::http::Response::builder()
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Access-Control-Allow-Methods", "GET, OPTIONS")
+        .header("Access-Control-Allow-Headers", "*")
+        .header("Cross-Origin-Resource-Policy", "cross-origin")
+        .header("Cache-Control", "no-cache")
+        .header("Accept-Ranges", "bytes")
+        .header("ETag", &tag);
+    if let Some(m) = modified {
+        builder = builder.header("Last-Modified", http_date(m));
+    }
+
+    if is_not_modified(headers, &tag, modified) {
+        return builder.status(304).body(Vec::new()).unwrap();
+    }
+
+    let len = body.as_ref().map_or(meta.len(), |b| b.len() as u64);
+    let range = header_str(headers, RANGE);
+    let builder = builder.header("Content-Type", mime);
+
+    match parse_range(range, len) {
+        RangeRequest::Full => {
+            let data = match body {
+                Some(b) => b,
+                None => match std::fs::read(file_path) {
+                    Ok(d) => d,
+                    Err(_) => return status(404, "Not found"),
+                },
+            };
+            builder
+                .header("Content-Length", data.len())
+                .body(data)
+                .unwrap()
+        }
+        RangeRequest::Partial(r) => {
+            let data = match body {
+                Some(b) => b[r.start as usize..=r.end as usize].to_vec(),
+                None => match read_range(file_path, r) {
+                    Ok(d) => d,
+                    Err(_) => return status(404, "Not found"),
+                },
+            };
+            builder
+                .status(206)
+                .header(
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", r.start, r.end, len),
+                )
+                .header("Content-Length", data.len())
+                .body(data)
+                .unwrap()
+        }
+        RangeRequest::Unsatisfiable => builder
+            .status(416)
+            .header("Content-Range", format!("bytes */{}", len))
+            .body(Vec::new())
+            .unwrap(),
+    }
+}
+
+fn read_range(path: &Path, range: ByteRange) -> std::io::Result<Vec<u8>> {
+    let mut file = std::fs::File::open(path)?;
+    file.seek(SeekFrom::Start(range.start))?;
+    let mut data = Vec::with_capacity(range.len() as usize);
+    file.take(range.len()).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn header_str(headers: &HeaderMap, name: HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Interpret a `Range` header for a body of `len` bytes.
+///
+/// Only single `bytes=` ranges are supported; multi-range and malformed
+/// headers are ignored and fall back to the full body, as RFC 9110 allows.
+pub fn parse_range(header: Option<&str>, len: u64) -> RangeRequest {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+
+    let range = match (start.trim(), end.trim()) {
+        // Suffix range: the last N bytes
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return RangeRequest::Unsatisfiable,
+            Ok(n) if len > 0 => ByteRange {
+                start: len.saturating_sub(n),
+                end: len - 1,
+            },
+            Ok(_) => return RangeRequest::Unsatisfiable,
+            Err(_) => return RangeRequest::Full,
+        },
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return RangeRequest::Full;
+            };
+            let end = if end.is_empty() {
+                u64::MAX
+            } else {
+                match end.parse::<u64>() {
+                    Ok(e) if e >= start => e,
+                    _ => return RangeRequest::Full,
+                }
+            };
+            if start >= len {
+                return RangeRequest::Unsatisfiable;
+            }
+            ByteRange {
+                start,
+                end: end.min(len - 1),
+            }
+        }
+    };
+    RangeRequest::Partial(range)
+}
+
+/// Check `If-None-Match` (preferred) or `If-Modified-Since` against the
+/// current validators.
+pub fn is_not_modified(headers: &HeaderMap, tag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(inm) = header_str(headers, IF_NONE_MATCH) {
+        let tag = tag.trim_start_matches("W/");
+        return inm
+            .split(',')
+            .map(|t| t.trim().trim_start_matches("W/"))
+            .any(|t| t == "*" || t == tag);
+    }
+    if let (Some(since), Some(modified)) = (header_str(headers, IF_MODIFIED_SINCE), modified) {
+        if let Ok(since) = chrono::DateTime::parse_from_rfc2822(since) {
+            let modified = chrono::DateTime::<chrono::Utc>::from(modified).timestamp();
+            return modified <= since.timestamp();
+        }
+    }
+    false
+}
+
+/// Weak validator derived from file size and modification time.
+fn etag(len: u64, modified: Option<SystemTime>) -> String {
+    let nanos = modified
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+    format!("W/\"{:x}-{:x}\"", len, nanos)
+}
+
+/// Format a timestamp as an RFC 9110 HTTP-date.
+fn http_date(t: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(t)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+// ---------------------------------------------------------------------------
+// Path resolution
+// ---------------------------------------------------------------------------
+
+/// Directories the handler may serve from: the app data `models` dir, which
+/// also holds the copies of custom previews, and the directory of every
+/// registered model.
+pub fn allowed_roots(app: &tauri::AppHandle, models: &[String]) -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Ok(dir) = app.path().app_data_dir() {
+        roots.push(dir.join("models"));
+    }
+    for model in models {
+        if let Some(parent) = Path::new(model).parent() {
+            roots.push(parent.to_path_buf());
+        }
+    }
+    roots
+}
+
+/// Extract the percent-decoded filesystem path from a
+/// `model://localhost/<absolute-path>` URI.
+pub fn uri_to_path(uri: &str) -> String {
+    let path = uri
+        .strip_prefix("model://localhost/")
+        .or_else(|| uri.strip_prefix("model://localhost"))
+        .unwrap_or("");
+    percent_encoding::percent_decode_str(path)
+        .decode_utf8_lossy()
+        .to_string()
+}
+
+/// Resolve `path` to a canonical file path that lies under one of `roots`.
+///
+/// Relative paths and any `..` component are rejected outright. Symlinks are
+/// followed by canonicalization, so a link pointing outside the roots is
+/// rejected as well. Missing files only report `NotFound` when they would
+/// have been inside a root, so the handler can't be used to probe the
+/// filesystem.
+pub fn resolve(path: &str, roots: &[PathBuf]) -> Result<PathBuf, ResolveError> {
+    let requested = Path::new(path);
+    if !requested.is_absolute()
+        || requested
+            .components()
+            .any(|c| matches!(c, Component::ParentDir))
+    {
+        return Err(ResolveError::Forbidden);
+    }
+
+    let canonical_roots: Vec<PathBuf> =
+        roots.iter().filter_map(|r| r.canonicalize().ok()).collect();
+
+    match requested.canonicalize() {
+        Ok(canonical) => {
+            if canonical.is_file() && canonical_roots.iter().any(|r| canonical.starts_with(r)) {
+                Ok(canonical)
+            } else {
+                Err(ResolveError::Forbidden)
+            }
+        }
+        Err(_) => {
+            let inside = roots
+                .iter()
+                .chain(canonical_roots.iter())
+                .any(|r| requested.starts_with(r));
+            if inside {
+                Err(ResolveError::NotFound)
+            } else {
+                Err(ResolveError::Forbidden)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (tempfile::TempDir, PathBuf) {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("models");
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        std::fs::write(root.join("a/model.model3.json"), "{}").unwrap();
+        std::fs::write(tmp.path().join("secret.txt"), "secret").unwrap();
+        (tmp, root)
+    }
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(
+            uri_to_path("model://localhost//home/user/a%20b/x.png"),
+            "/home/user/a b/x.png"
+        );
+        assert_eq!(uri_to_path("model://localhost"), "");
+    }
+
+    #[test]
+    fn test_resolve_inside_root() {
+        let (_tmp, root) = setup();
+        let file = root.join("a/model.model3.json");
+        let resolved = resolve(file.to_str().unwrap(), std::slice::from_ref(&root)).unwrap();
+        assert_eq!(resolved, file.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_resolve_outside_root() {
+        let (tmp, root) = setup();
+        let secret = tmp.path().join("secret.txt");
+        assert_eq!(
+            resolve(secret.to_str().unwrap(), &[root]),
+            Err(ResolveError::Forbidden)
+        );
+    }
+
+    #[test]
+    fn test_resolve_rejects_parent_dir() {
+        let (_tmp, root) = setup();
+        let escaped = format!("{}/a/../../secret.txt", root.display());
+        assert_eq!(resolve(&escaped, &[root]), Err(ResolveError::Forbidden));
+    }
+
+    #[test]
+    fn test_resolve_rejects_relative() {
+        let (_tmp, root) = setup();
+        assert_eq!(
+            resolve("a/model.model3.json", &[root]),
+            Err(ResolveError::Forbidden)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_symlink_escape() {
+        let (tmp, root) = setup();
+        let link = root.join("a/link.txt");
+        std::os::unix::fs::symlink(tmp.path().join("secret.txt"), &link).unwrap();
+        assert_eq!(
+            resolve(link.to_str().unwrap(), &[root]),
+            Err(ResolveError::Forbidden)
+        );
+    }
+
+    #[test]
+    fn test_resolve_missing_file() {
+        let (tmp, root) = setup();
+        let missing = root.join("a/missing.png");
+        assert_eq!(
+            resolve(missing.to_str().unwrap(), std::slice::from_ref(&root)),
+            Err(ResolveError::NotFound)
+        );
+        let outside = tmp.path().join("missing.png");
+        assert_eq!(
+            resolve(outside.to_str().unwrap(), &[root]),
+            Err(ResolveError::Forbidden)
+        );
+    }
+
+    #[test]
+    fn test_resolve_single_file_root() {
+        let (tmp, root) = setup();
+        let secret = tmp.path().join("secret.txt");
+        assert!(resolve(secret.to_str().unwrap(), &[root, secret.clone()]).is_ok());
+    }
+
+    fn headers(pairs: &[(HeaderName, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (k, v) in pairs {
+            map.insert(k.clone(), v.parse().unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let r = |start, end| RangeRequest::Partial(ByteRange { start, end });
+        assert_eq!(parse_range(None, 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("bytes=0-9"), 100), r(0, 9));
+        assert_eq!(parse_range(Some("bytes=90-"), 100), r(90, 99));
+        assert_eq!(parse_range(Some("bytes=90-500"), 100), r(90, 99));
+        assert_eq!(parse_range(Some("bytes=-10"), 100), r(90, 99));
+        assert_eq!(parse_range(Some("bytes=-500"), 100), r(0, 99));
+        assert_eq!(
+            parse_range(Some("bytes=100-"), 100),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-0"), 100),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range(Some("bytes=0-1"), 0),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(parse_range(Some("bytes=5-2"), 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("items=0-1"), 100), RangeRequest::Full);
+    }
+
+    fn audio_file() -> (tempfile::TempDir, PathBuf) {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("voice.ogg");
+        std::fs::write(&file, (0u8..100).collect::<Vec<u8>>()).unwrap();
+        (tmp, file)
+    }
+
+    #[test]
+    fn test_serve_full() {
+        let (_tmp, file) = audio_file();
+        let resp = serve_file(&HeaderMap::new(), &file, "audio/ogg", None);
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["Content-Length"], "100");
+        assert_eq!(resp.headers()["Accept-Ranges"], "bytes");
+        assert!(resp.headers().contains_key("ETag"));
+        assert!(resp.headers().contains_key("Last-Modified"));
+        assert_eq!(resp.body().len(), 100);
+    }
+
+    #[test]
+    fn test_serve_partial() {
+        let (_tmp, file) = audio_file();
+        let req = headers(&[(RANGE, "bytes=10-19")]);
+        let resp = serve_file(&req, &file, "audio/ogg", None);
+        assert_eq!(resp.status(), 206);
+        assert_eq!(resp.headers()["Content-Range"], "bytes 10-19/100");
+        assert_eq!(resp.headers()["Content-Length"], "10");
+        assert_eq!(resp.body(), &(10u8..20).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_serve_partial_patched_body() {
+        let (_tmp, file) = audio_file();
+        let req = headers(&[(RANGE, "bytes=-3")]);
+        let resp = serve_file(&req, &file, "application/json", Some(b"{\"a\":1}".to_vec()));
+        assert_eq!(resp.status(), 206);
+        assert_eq!(resp.headers()["Content-Range"], "bytes 4-6/7");
+        assert_eq!(resp.body(), b":1}");
+    }
+
+    #[test]
+    fn test_serve_unsatisfiable() {
+        let (_tmp, file) = audio_file();
+        let req = headers(&[(RANGE, "bytes=200-")]);
+        let resp = serve_file(&req, &file, "audio/ogg", None);
+        assert_eq!(resp.status(), 416);
+        assert_eq!(resp.headers()["Content-Range"], "bytes */100");
+    }
+
+    #[test]
+    fn test_serve_not_modified() {
+        let (_tmp, file) = audio_file();
+        let first = serve_file(&HeaderMap::new(), &file, "audio/ogg", None);
+        let tag = first.headers()["ETag"].to_str().unwrap().to_string();
+        let last_modified = first.headers()["Last-Modified"]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let req = headers(&[(IF_NONE_MATCH, &tag)]);
+        let resp = serve_file(&req, &file, "audio/ogg", None);
+        assert_eq!(resp.status(), 304);
+        assert!(resp.body().is_empty());
+
+        let req = headers(&[(IF_MODIFIED_SINCE, &last_modified)]);
+        assert_eq!(serve_file(&req, &file, "audio/ogg", None).status(), 304);
+
+        let req = headers(&[(IF_NONE_MATCH, "\"stale\"")]);
+        assert_eq!(serve_file(&req, &file, "audio/ogg", None).status(), 200);
+
+        let req = headers(&[(IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:00 GMT")]);
+        assert_eq!(serve_file(&req, &file, "audio/ogg", None).status(), 200);
+    }
+
+    #[test]
+    fn test_patched_body_revalidates_after_fixup_toggle() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("a.model3.json");
+        std::fs::write(&file, r#"{"FileReferences":{}}"#).unwrap();
+        let registry = crate::fixups::Registry::builtin();
+        let serve = |req: &HeaderMap, flags: &std::collections::HashMap<String, String>| {
+            let data = std::fs::read(&file).unwrap();
+            let (body, _) = registry.run(&file, data, flags);
+            serve_file(req, &file, "application/json", Some(body))
+        };
+
+        let enabled = std::collections::HashMap::new();
+        let first = serve(&HeaderMap::new(), &enabled);
+        assert!(!first.headers().contains_key("Last-Modified"));
+        let tag = first.headers()["ETag"].to_str().unwrap().to_string();
+        let req = headers(&[(IF_NONE_MATCH, &tag)]);
+        assert_eq!(serve(&req, &enabled).status(), 304);
+
+        // Same file on disk, different patched body
+        let disabled = std::collections::HashMap::from([(
+            "fixup:model3_groups".to_string(),
+            "false".to_string(),
+        )]);
+        let resp = serve(&req, &disabled);
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.body(), br#"{"FileReferences":{}}"#);
+    }
+}
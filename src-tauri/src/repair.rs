@@ -0,0 +1,289 @@
+use crate::descriptor::ModelDescriptor;
+use crate::fixups::{self, Applied, Registry};
+use crate::model3::Model3;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Config key prefix marking a file whose fixes were written to disk
+/// (`repaired:<file path>` = comma-separated ids of the fixups enabled when
+/// it was repaired). The `model://` handler skips those fixups for that
+/// file only, so fixups enabled later still apply.
+pub const SETTING_PREFIX: &str = "repaired:";
+
+/// Suffix of the backup kept next to each rewritten file.
+pub const BACKUP_SUFFIX: &str = ".orig";
+
+/// A file rewritten by `repair_model`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairedFile {
+    /// Path relative to the model directory
+    pub file: String,
+    #[serde(skip)]
+    pub path: PathBuf,
+    pub backup: String,
+    pub applied: Vec<Applied>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairReport {
+    pub files: Vec<RepairedFile>,
+}
+
+/// Run the enabled fixups over the model descriptor at `path` and its
+/// motion files, rewriting changed files in place.
+///
+/// The original of every changed file is kept as `<file>.orig`; an existing
+/// backup is never overwritten, so it always holds the pre-repair content.
+pub fn repair_model(
+    path: &Path,
+    registry: &Registry,
+    flags: &HashMap<String, String>,
+) -> Result<RepairReport, String> {
+    let dir = path.parent().ok_or("Invalid model path")?;
+    let desc = ModelDescriptor::load(path)?;
+
+    let mut targets = vec![path.canonicalize().map_err(|e| e.to_string())?];
+    let motion_files: BTreeSet<&str> = desc
+        .motions
+        .values()
+        .flatten()
+        .filter_map(|m| m.file.as_deref())
+        .filter(|f| !f.is_empty())
+        .collect();
+    // Never rewrite files outside the model directory
+    let root = dir.canonicalize().map_err(|e| e.to_string())?;
+    targets.extend(
+        motion_files
+            .into_iter()
+            .filter_map(|f| dir.join(f).canonicalize().ok())
+            .filter(|p| p.starts_with(&root)),
+    );
+
+    let mut files = Vec::new();
+    for target in targets {
+        let Ok(data) = std::fs::read(&target) else {
+            continue;
+        };
+        let (fixed, applied) = registry.run(&target, data.clone(), flags);
+        if applied.is_empty() {
+            continue;
+        }
+
+        let name = target.file_name().unwrap_or_default().to_string_lossy();
+        let backup = target.with_file_name(format!("{}{}", name, BACKUP_SUFFIX));
+        if !backup.exists() {
+            write_atomic(&backup, &data)?;
+        }
+        write_atomic(&target, &pretty(&target, &fixed)?)?;
+
+        files.push(RepairedFile {
+            file: relative(&root, &target),
+            path: target,
+            backup: relative(&root, &backup),
+            applied,
+        });
+    }
+
+    Ok(RepairReport { files })
+}
+
+/// Config entries marking the files in `report` as repaired with the
+/// fixups enabled in `flags`.
+pub fn repaired_settings(
+    report: &RepairReport,
+    registry: &Registry,
+    flags: &HashMap<String, String>,
+) -> Vec<(String, String)> {
+    let enabled: Vec<_> = registry
+        .list(flags)
+        .into_iter()
+        .filter(|f| f.enabled)
+        .map(|f| f.id)
+        .collect();
+    report
+        .files
+        .iter()
+        .map(|f| {
+            (
+                format!("{}{}", SETTING_PREFIX, f.path.to_string_lossy()),
+                enabled.join(","),
+            )
+        })
+        .collect()
+}
+
+/// Turn off in `flags` the fixups already written to a file, given its
+/// `repaired:` entry.
+pub fn skip_repaired(flags: &mut HashMap<String, String>, repaired: &str) {
+    for id in repaired.split(',').filter(|id| !id.is_empty()) {
+        flags.insert(
+            format!("{}{}", fixups::SETTING_PREFIX, id),
+            "false".to_string(),
+        );
+    }
+}
+
+/// Write `data` to a temporary file next to `path` and rename it over
+/// `path`, so an interrupted write never leaves a half-written file.
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.tmp", name));
+    std::fs::write(&tmp, data).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, path).map_err(|e| {
+        std::fs::remove_file(&tmp).ok();
+        e.to_string()
+    })
+}
+
+/// Re-indent fixup output for writing to disk. Descriptors go through the
+/// typed model, unless a field has the wrong type for it; other files keep
+/// their key order through `serde_json`'s `preserve_order`.
+fn pretty(path: &Path, data: &[u8]) -> Result<Vec<u8>, String> {
+    let is_model3 = path
+        .to_str()
+        .map(|p| p.ends_with(".model3.json"))
+        .unwrap_or(false);
+    if let Some(model) = is_model3.then(|| Model3::from_slice(data).ok()).flatten() {
+        return model.to_vec_pretty();
+    }
+    let json: Value = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+    serde_json::to_vec_pretty(&json).map_err(|e| e.to_string())
+}
+
+fn relative(dir: &Path, path: &Path) -> String {
+    path.strip_prefix(dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOTION: &str = r#"{"Meta":{"TotalPointCount":99,"TotalSegmentCount":1},
+        "Curves":[{"Segments":[0,0, 0,1,1]}]}"#;
+
+    fn setup() -> (tempfile::TempDir, std::path::PathBuf) {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("motions")).unwrap();
+        std::fs::write(dir.join("motions/idle.json"), MOTION).unwrap();
+        let model = dir.join("a.model3.json");
+        std::fs::write(
+            &model,
+            r#"{"Version":3,"FileReferences":{"Moc":"a.moc3","Textures":["t.png",""],
+                "Motions":{"Idle":[{"File":"motions/idle.json"},{"File":"motions/idle.json"}]}}}"#,
+        )
+        .unwrap();
+        (tmp, model)
+    }
+
+    #[test]
+    fn test_repair_writes_fixes_and_backups() {
+        let (tmp, model) = setup();
+        let original = std::fs::read(&model).unwrap();
+
+        let report = repair_model(&model, &Registry::builtin(), &HashMap::new()).unwrap();
+        let summary: Vec<_> = report
+            .files
+            .iter()
+            .map(|f| (f.file.as_str(), f.applied.len()))
+            .collect();
+        assert_eq!(summary, [("a.model3.json", 2), ("motions/idle.json", 1)]);
+        assert_eq!(report.files[1].backup, "motions/idle.json.orig");
+
+        let fixed = Model3::from_slice(&std::fs::read(&model).unwrap()).unwrap();
+        assert_eq!(fixed.textures(), ["t.png"]);
+        assert!(fixed.groups.is_some());
+        assert_eq!(
+            std::fs::read(tmp.path().join("a.model3.json.orig")).unwrap(),
+            original
+        );
+
+        let motion: Value =
+            serde_json::from_slice(&std::fs::read(tmp.path().join("motions/idle.json")).unwrap())
+                .unwrap();
+        assert_eq!(motion["Meta"]["TotalPointCount"], 2);
+        // Keys stay in the order the file had them
+        let keys: Vec<_> = motion.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["Meta", "Curves"]);
+    }
+
+    #[test]
+    fn test_repaired_fixups_skipped_per_file() {
+        let (tmp, model) = setup();
+        let registry = Registry::builtin();
+        // Repaired with motion counts off: only the descriptor is rewritten
+        let flags = HashMap::from([("fixup:motion3_counts".to_string(), "false".to_string())]);
+        let report = repair_model(&model, &registry, &flags).unwrap();
+        let settings = repaired_settings(&report, &registry, &flags);
+        let canonical = model.canonicalize().unwrap();
+        assert_eq!(
+            settings,
+            [(
+                format!("repaired:{}", canonical.display()),
+                "model3_groups,model3_empty_textures".to_string()
+            )]
+        );
+        // No temporary files are left behind
+        let mut names: Vec<_> = std::fs::read_dir(tmp.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a.model3.json", "a.model3.json.orig", "motions"]);
+
+        // Motion counts enabled later still apply to the repaired file
+        let mut flags = HashMap::new();
+        skip_repaired(&mut flags, &settings[0].1);
+        let mut data = std::fs::read(&model).unwrap();
+        let (_, applied) = registry.run(&canonical, data.clone(), &flags);
+        assert!(applied.is_empty());
+        data = br#"{"Meta":{"TotalPointCount":1},"Curves":[]}"#.to_vec();
+        let (_, applied) = registry.run(Path::new("/m/x.json"), data, &flags);
+        assert_eq!(applied.len(), 1);
+    }
+
+    #[test]
+    fn test_repair_is_idempotent() {
+        let (tmp, model) = setup();
+        let original = std::fs::read(&model).unwrap();
+        repair_model(&model, &Registry::builtin(), &HashMap::new()).unwrap();
+
+        let report = repair_model(&model, &Registry::builtin(), &HashMap::new()).unwrap();
+        assert!(report.files.is_empty());
+        assert_eq!(
+            std::fs::read(tmp.path().join("a.model3.json.orig")).unwrap(),
+            original
+        );
+    }
+
+    #[test]
+    fn test_repair_stays_in_model_dir() {
+        let (tmp, _) = setup();
+        let outside = tmp.path().join("outside.json");
+        std::fs::write(&outside, MOTION).unwrap();
+        let model = tmp.path().join("motions/b.model3.json");
+        std::fs::write(
+            &model,
+            r#"{"FileReferences":{"Motions":{"Idle":[{"File":"../outside.json"}]}},"Groups":[]}"#,
+        )
+        .unwrap();
+
+        let report = repair_model(&model, &Registry::builtin(), &HashMap::new()).unwrap();
+        assert!(report.files.is_empty());
+        assert_eq!(std::fs::read_to_string(&outside).unwrap(), MOTION);
+    }
+
+    #[test]
+    fn test_repair_respects_disabled_fixups() {
+        let (tmp, model) = setup();
+        let flags = HashMap::from([("fixup:motion3_counts".to_string(), "false".to_string())]);
+        let report = repair_model(&model, &Registry::builtin(), &flags).unwrap();
+        assert_eq!(report.files.len(), 1);
+        assert!(!tmp.path().join("motions/idle.json.orig").exists());
+    }
+}
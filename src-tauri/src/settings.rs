@@ -0,0 +1,333 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A type settings are stored as. Values are kept as strings in the
+/// config table.
+pub trait SettingValue: Sized {
+    fn parse(value: &str) -> Result<Self, String>;
+}
+
+impl SettingValue for bool {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(format!("Expected true or false, got {:?}", value)),
+        }
+    }
+}
+
+impl SettingValue for f64 {
+    fn parse(value: &str) -> Result<Self, String> {
+        value
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| format!("Expected a number, got {:?}", value))
+    }
+}
+
+macro_rules! impl_integer_setting {
+    ($($ty:ty),*) => {$(
+        impl SettingValue for $ty {
+            fn parse(value: &str) -> Result<Self, String> {
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Expected a whole number, got {:?}", value))
+            }
+        }
+    )*};
+}
+
+impl_integer_setting!(u64, usize);
+
+impl SettingValue for String {
+    fn parse(value: &str) -> Result<Self, String> {
+        Ok(value.to_string())
+    }
+}
+
+/// Unset until stored; a stored value must parse as `T`.
+impl<T: SettingValue> SettingValue for Option<T> {
+    fn parse(value: &str) -> Result<Self, String> {
+        T::parse(value).map(Some)
+    }
+}
+
+fn positive(value: &Option<f64>) -> Result<(), String> {
+    match value {
+        Some(v) if *v <= 0.0 => Err("Must be greater than zero".to_string()),
+        _ => Ok(()),
+    }
+}
+
+fn nonzero<T: PartialEq + Default>(value: &Option<T>) -> Result<(), String> {
+    match value {
+        Some(v) if *v == T::default() => Err("Must not be zero".to_string()),
+        _ => Ok(()),
+    }
+}
+
+fn not_empty(value: &Option<String>) -> Result<(), String> {
+    match value {
+        Some(v) if v.trim().is_empty() => Err("Must not be empty".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Extra validation of a parsed setting value.
+type Check<T> = fn(&T) -> Result<(), String>;
+
+/// The optional check of a `settings!` entry as a function pointer.
+macro_rules! check {
+    ($ty:ty) => {
+        None
+    };
+    ($ty:ty, $check:expr) => {
+        Some($check as Check<$ty>)
+    };
+}
+
+/// Declares the global settings: the `Settings` struct, its defaults,
+/// `KEYS` and the per-key validation in `validate`.
+macro_rules! settings {
+    ($(
+        $(#[doc = $doc:literal])*
+        $key:ident: $ty:ty = $default:expr $(, check = $check:expr)?;
+    )*) => {
+        /// Global settings, typed and with defaults applied.
+        #[derive(Debug, Clone, PartialEq, Serialize)]
+        pub struct Settings {
+            $($(#[doc = $doc])* pub $key: $ty,)*
+        }
+
+        impl Default for Settings {
+            fn default() -> Self {
+                Self {
+                    $($key: $default,)*
+                }
+            }
+        }
+
+        /// Config keys of all global settings.
+        pub const KEYS: &[&str] = &[$(stringify!($key)),*];
+
+        impl Settings {
+            /// Settings from stored `key -> value` pairs. Missing or invalid
+            /// values fall back to their default.
+            pub fn from_values(values: &HashMap<String, String>) -> Self {
+                let mut settings = Self::default();
+                $(
+                    if let Some(value) = values.get(stringify!($key)) {
+                        match parse::<$ty>(value, check!($ty $(, $check)?)) {
+                            Ok(value) => settings.$key = value,
+                            Err(e) => eprintln!(
+                                "[rive2d] ignoring setting {}: {}",
+                                stringify!($key),
+                                e
+                            ),
+                        }
+                    }
+                )*
+                settings
+            }
+        }
+
+        fn validate_global(key: &str, value: &str) -> Option<Result<(), String>> {
+            match key {
+                $(stringify!($key) => Some(parse::<$ty>(value, check!($ty $(, $check)?)).map(|_| ())),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+/// Parse `value` and run the key's check on it, if it has one.
+fn parse<T: SettingValue>(value: &str, check: Option<Check<T>>) -> Result<T, String> {
+    let value = T::parse(value)?;
+    if let Some(check) = check {
+        check(&value)?;
+    }
+    Ok(value)
+}
+
+settings! {
+    /// Draw a border around the pet window
+    show_border: bool = false;
+    /// Saved model position, in window pixels
+    model_x: Option<f64> = None;
+    model_y: Option<f64> = None;
+    model_scale: Option<f64> = None, check = positive;
+    /// Play a motion when a hit area is tapped
+    tap_motion: bool = true;
+    show_hit_areas: bool = false;
+    /// Ignore drags and scrolls on the model
+    lock_model: bool = false;
+    /// Turn the model towards the cursor
+    mouse_tracking: bool = true;
+    /// config.json (or a directory holding one) for STM archives
+    stm_config_path: Option<String> = None, check = not_empty;
+    /// Worker threads for folder imports
+    import_workers: Option<usize> = None, check = nonzero;
+    /// Overrides of the archive limits, see `lpk::ExtractLimits`
+    import_max_total_size: Option<u64> = None, check = nonzero;
+    import_max_entries: Option<usize> = None, check = nonzero;
+    import_max_ratio: Option<u64> = None, check = nonzero;
+}
+
+/// Settings a model can override; the global value is its fallback.
+pub const PER_MODEL: &[&str] = &[
+    "model_x",
+    "model_y",
+    "model_scale",
+    "tap_motion",
+    "mouse_tracking",
+    "lock_model",
+];
+
+impl Settings {
+    /// Settings for one model: `values` with the model's `overrides` of
+    /// `PER_MODEL` keys applied on top.
+    pub fn with_overrides(
+        values: &HashMap<String, String>,
+        overrides: &HashMap<String, String>,
+    ) -> Self {
+        let mut values = values.clone();
+        for key in PER_MODEL {
+            if let Some(value) = overrides.get(*key) {
+                values.insert(key.to_string(), value.clone());
+            }
+        }
+        Self::from_values(&values)
+    }
+
+    /// Value of the setting `key` as it would be stored; `None` for unknown
+    /// or unset keys.
+    pub fn get(&self, key: &str) -> Option<String> {
+        match serde_json::to_value(self).ok()?.get(key)? {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(s) => Some(s.clone()),
+            value => Some(value.to_string()),
+        }
+    }
+}
+
+/// Check a `set_setting` call from the frontend: `key` must be a known
+/// setting and `value` valid for it.
+pub fn validate(key: &str, value: &str) -> Result<(), String> {
+    validate_global(key, value)
+        .ok_or_else(|| format!("Unknown setting: {}", key))?
+        .map_err(|e| format!("Invalid value for {}: {}", key, e))
+}
+
+/// Like `validate`, for a model's override of `key`.
+pub fn validate_per_model(key: &str, value: &str) -> Result<(), String> {
+    if !PER_MODEL.contains(&key) {
+        return Err(format!("Not a per-model setting: {}", key));
+    }
+    validate(key, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_values_applies_defaults() {
+        assert_eq!(Settings::from_values(&HashMap::new()), Settings::default());
+
+        let settings = Settings::from_values(&values(&[
+            ("show_border", "true"),
+            ("tap_motion", "false"),
+            ("model_x", "12.5"),
+            ("model_scale", "-1"),
+            ("mouse_tracking", "yes"),
+            ("import_workers", "3"),
+            ("unrelated", "1"),
+        ]));
+        assert!(settings.show_border);
+        assert!(!settings.tap_motion);
+        assert_eq!(settings.model_x, Some(12.5));
+        // Invalid stored values fall back to the default
+        assert_eq!(settings.model_scale, None);
+        assert!(settings.mouse_tracking);
+        assert_eq!(settings.import_workers, Some(3));
+    }
+
+    #[test]
+    fn test_validate() {
+        for (key, value) in [
+            ("show_border", "true"),
+            ("model_scale", "0.5"),
+            ("model_x", "-20"),
+            ("import_workers", "2"),
+        ] {
+            assert!(validate(key, value).is_ok(), "{} = {}", key, value);
+        }
+        for (key, value) in [
+            ("show_border", "1"),
+            ("model_scale", "0"),
+            ("model_x", "NaN"),
+            ("import_workers", "0"),
+            ("import_max_entries", "-5"),
+            ("stm_config_path", " "),
+            ("intimacy:/a.model3.json", "40"),
+            ("current_model", "/a.model3.json"),
+            ("name:/a.model3.json", "A"),
+        ] {
+            assert!(validate(key, value).is_err(), "{} = {}", key, value);
+        }
+    }
+
+    #[test]
+    fn test_with_overrides() {
+        let global = values(&[
+            ("tap_motion", "false"),
+            ("show_border", "true"),
+            ("model_scale", "0.4"),
+        ]);
+        let overrides = values(&[
+            ("tap_motion", "true"),
+            ("model_x", "10"),
+            ("model_scale", "bad"),
+            // Not overridable per model
+            ("show_border", "false"),
+        ]);
+        let settings = Settings::with_overrides(&global, &overrides);
+        assert!(settings.tap_motion);
+        assert!(settings.show_border);
+        assert_eq!(settings.model_x, Some(10.0));
+        // An invalid override falls back to the default, like a bad value
+        assert_eq!(settings.model_scale, None);
+        assert!(!Settings::with_overrides(&global, &HashMap::new()).tap_motion);
+
+        assert_eq!(settings.get("tap_motion").as_deref(), Some("true"));
+        assert_eq!(settings.get("model_x").as_deref(), Some("10.0"));
+        assert_eq!(settings.get("model_y"), None);
+        assert_eq!(settings.get("unknown"), None);
+
+        assert!(validate_per_model("lock_model", "true").is_ok());
+        assert!(validate_per_model("lock_model", "maybe").is_err());
+        assert!(validate_per_model("show_border", "true").is_err());
+    }
+
+    #[test]
+    fn test_keys_cover_settings() {
+        let json = serde_json::to_value(Settings::default()).unwrap();
+        let mut fields: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        fields.sort();
+        let mut keys: Vec<_> = KEYS.iter().map(|k| k.to_string()).collect();
+        keys.sort();
+        assert_eq!(fields, keys);
+        assert!(PER_MODEL.iter().all(|k| KEYS.contains(k)));
+    }
+}
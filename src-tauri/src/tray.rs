@@ -23,10 +23,12 @@ pub fn setup_tray(app: &tauri::AppHandle) -> Result<(), Box<dyn std::error::Erro
             "settings" => {
                 crate::create_config_window(app);
             }
-            "reset_position" => {
-                crate::config::delete_settings(app, &["model_x", "model_y", "model_scale"]);
-                app.emit("reset-position", ()).ok();
-            }
+            "reset_position" => match crate::reset_position(app) {
+                Ok(()) => {
+                    app.emit("reset-position", ()).ok();
+                }
+                Err(e) => eprintln!("[rive2d] failed to reset position: {}", e),
+            },
             "quit" => {
                 app.exit(0);
             }
@@ -0,0 +1,695 @@
+use crate::descriptor::{detect_version, CubismVersion};
+use crate::fixups;
+use crate::model2::Model2;
+use crate::model3::{Model3, Motion};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The model will fail to load or render
+    Error,
+    /// Part of the model will not work (a motion, sound, hit area, ...)
+    Warning,
+    /// Deviates from the spec but viewers cope with it
+    Info,
+}
+
+/// A single problem found in a model.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable machine-readable kind, e.g. "missing-file"
+    pub code: &'static str,
+    /// File the pointer refers to, relative to the model directory
+    pub file: String,
+    /// RFC 6901 JSON pointer into `file`
+    pub pointer: String,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+/// Suggested automatic repair, backed by a fixup from `fixups`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Fix {
+    pub fixup: &'static str,
+    pub description: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    pub version: CubismVersion,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Load the model descriptor at `path` and check it and the files it
+/// references. Only unreadable or unparseable descriptors are an `Err`.
+pub fn validate_model(path: &Path) -> Result<ValidationReport, String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let json: Value = serde_json::from_slice(&data).map_err(|e| e.to_string())?;
+    let version = detect_version(&json);
+
+    let mut v = Validator {
+        dir: path.parent().unwrap_or(Path::new("")),
+        file: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        diagnostics: Vec::new(),
+    };
+    let dropped = match version {
+        CubismVersion::Cubism3 => {
+            let (model, dropped) = Model3::from_slice_lenient(&data)?;
+            v.check_model3(&model);
+            dropped
+        }
+        CubismVersion::Cubism2 => {
+            let (model, dropped) = Model2::from_slice_lenient(&data)?;
+            v.check_model2(&model);
+            dropped
+        }
+    };
+    // A null texture already shows up as an empty one
+    for pointer in dropped {
+        if !v.diagnostics.iter().any(|d| d.pointer == pointer) {
+            v.push(
+                Severity::Warning,
+                "invalid-type",
+                pointer,
+                "Value has the wrong type and is ignored".to_string(),
+            );
+        }
+    }
+
+    Ok(ValidationReport {
+        version,
+        diagnostics: v.diagnostics,
+    })
+}
+
+struct Validator<'a> {
+    dir: &'a Path,
+    /// Descriptor file name
+    file: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn push(&mut self, severity: Severity, code: &'static str, pointer: String, message: String) {
+        self.push_in(self.file.clone(), severity, code, pointer, message, None);
+    }
+
+    fn push_in(
+        &mut self,
+        file: String,
+        severity: Severity,
+        code: &'static str,
+        pointer: String,
+        message: String,
+        fix: Option<Fix>,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code,
+            file,
+            pointer,
+            message,
+            fix,
+        });
+    }
+
+    /// Report `rel` if it does not exist next to the descriptor.
+    /// Returns whether the file exists.
+    fn check_file(&mut self, severity: Severity, pointer: String, rel: &str) -> bool {
+        if self.dir.join(rel).is_file() {
+            return true;
+        }
+        self.push(
+            severity,
+            "missing-file",
+            pointer,
+            format!("Referenced file not found: {}", rel),
+        );
+        false
+    }
+
+    fn check_optional_file(&mut self, severity: Severity, pointer: String, rel: Option<&str>) {
+        if let Some(rel) = rel.filter(|r| !r.is_empty()) {
+            self.check_file(severity, pointer, rel);
+        }
+    }
+
+    fn check_duplicate_ids<'s>(&mut self, ids: impl Iterator<Item = (String, &'s str)>) {
+        let mut seen = HashMap::new();
+        for (pointer, id) in ids {
+            if id.is_empty() {
+                continue;
+            }
+            if let Some(first) = seen.insert(id, pointer.clone()) {
+                self.push(
+                    Severity::Warning,
+                    "duplicate-hit-area",
+                    pointer,
+                    format!("Hit area id \"{}\" is already used at {}", id, first),
+                );
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------
+    // Cubism 3/4
+    // -----------------------------------------------------------------------
+
+    fn check_model3(&mut self, model: &Model3) {
+        let Some(fr) = &model.file_references else {
+            self.push(
+                Severity::Error,
+                "missing-field",
+                "/FileReferences".to_string(),
+                "FileReferences is missing".to_string(),
+            );
+            return;
+        };
+
+        match fr.moc.as_deref().filter(|m| !m.is_empty()) {
+            Some(moc) => {
+                self.check_file(Severity::Error, "/FileReferences/Moc".to_string(), moc);
+            }
+            None => self.push(
+                Severity::Error,
+                "missing-field",
+                "/FileReferences/Moc".to_string(),
+                "No Moc file is referenced".to_string(),
+            ),
+        }
+
+        for (i, texture) in model.textures().iter().enumerate() {
+            let pointer = format!("/FileReferences/Textures/{}", i);
+            if texture.is_empty() {
+                self.push_in(
+                    self.file.clone(),
+                    Severity::Error,
+                    "empty-texture",
+                    pointer,
+                    "Texture entry is empty".to_string(),
+                    Some(Fix {
+                        fixup: "model3_empty_textures",
+                        description: "Remove empty texture entries",
+                    }),
+                );
+            } else {
+                self.check_file(Severity::Error, pointer, texture);
+            }
+        }
+
+        self.check_optional_file(
+            Severity::Warning,
+            "/FileReferences/Physics".to_string(),
+            fr.physics.as_deref(),
+        );
+        if let Some(physics) = &fr.physics_v2 {
+            self.check_optional_file(
+                Severity::Warning,
+                "/FileReferences/PhysicsV2/File".to_string(),
+                physics.file.as_deref(),
+            );
+        }
+        self.check_optional_file(
+            Severity::Warning,
+            "/FileReferences/Pose".to_string(),
+            fr.pose.as_deref(),
+        );
+        for (i, exp) in fr.expressions.iter().flatten().enumerate() {
+            self.check_optional_file(
+                Severity::Warning,
+                format!("/FileReferences/Expressions/{}/File", i),
+                exp.file.as_deref(),
+            );
+        }
+
+        let empty = BTreeMap::new();
+        let motions = model.motions().unwrap_or(&empty);
+        for (group, entries) in motions {
+            for (i, motion) in entries.iter().enumerate() {
+                let base = format!("/FileReferences/Motions/{}/{}", escape(group), i);
+                self.check_motion3(motions, &base, motion);
+            }
+        }
+
+        for (i, area) in model.hit_areas().iter().enumerate() {
+            self.check_motion_ref(
+                motions,
+                format!("/HitAreas/{}/Motion", i),
+                area.motion.as_deref(),
+            );
+        }
+        self.check_duplicate_ids(
+            model
+                .hit_areas()
+                .iter()
+                .enumerate()
+                .filter_map(|(i, a)| Some((format!("/HitAreas/{}/Id", i), a.id.as_deref()?))),
+        );
+
+        self.check_controller_refs(model, motions);
+
+        if model.groups.is_none() {
+            self.push_in(
+                self.file.clone(),
+                Severity::Info,
+                "missing-field",
+                "/Groups".to_string(),
+                "Groups is missing".to_string(),
+                Some(Fix {
+                    fixup: "model3_groups",
+                    description: "Add an empty Groups array",
+                }),
+            );
+        }
+    }
+
+    fn check_motion3(&mut self, motions: &BTreeMap<String, Vec<Motion>>, base: &str, m: &Motion) {
+        if let Some(file) = m.file.as_deref().filter(|f| !f.is_empty()) {
+            if self.check_file(Severity::Warning, format!("{}/File", base), file) {
+                self.check_motion_counts(file);
+            }
+        }
+        self.check_optional_file(
+            Severity::Warning,
+            format!("{}/Sound", base),
+            m.sound.as_deref(),
+        );
+        self.check_motion_ref(motions, format!("{}/NextMtn", base), m.next_mtn.as_deref());
+        for (j, choice) in m.choices.iter().flatten().enumerate() {
+            self.check_motion_ref(
+                motions,
+                format!("{}/Choices/{}/NextMtn", base, j),
+                choice.next_mtn.as_deref(),
+            );
+        }
+    }
+
+    /// Compare a motion3.json's Meta counts against its curves.
+    fn check_motion_counts(&mut self, rel: &str) {
+        let Ok(data) = std::fs::read(self.dir.join(rel)) else {
+            return;
+        };
+        let json: Value = match serde_json::from_slice(&data) {
+            Ok(json) => json,
+            Err(e) => {
+                self.push_in(
+                    rel.to_string(),
+                    Severity::Warning,
+                    "invalid-json",
+                    String::new(),
+                    format!("Motion file is not valid JSON: {}", e),
+                    None,
+                );
+                return;
+            }
+        };
+        let Some(meta) = json.get("Meta") else {
+            return;
+        };
+        let Some(curves) = json.get("Curves").and_then(|c| c.as_array()) else {
+            return;
+        };
+        let (points, segments) = fixups::count_motion_points(curves);
+
+        for (key, expected) in [("TotalPointCount", points), ("TotalSegmentCount", segments)] {
+            let Some(actual) = meta.get(key) else {
+                continue;
+            };
+            if actual.as_u64() == Some(expected) {
+                continue;
+            }
+            self.push_in(
+                rel.to_string(),
+                Severity::Warning,
+                "motion-counts",
+                format!("/Meta/{}", key),
+                format!("{} is {}, curves have {}", key, actual, expected),
+                Some(Fix {
+                    fixup: "motion3_counts",
+                    description: "Recompute TotalPointCount/TotalSegmentCount",
+                }),
+            );
+        }
+    }
+
+    /// Report a "Group" or "Group:Name" reference that resolves to nothing.
+    fn check_motion_ref(
+        &mut self,
+        motions: &BTreeMap<String, Vec<Motion>>,
+        pointer: String,
+        reference: Option<&str>,
+    ) {
+        let Some(reference) = reference.filter(|r| !r.is_empty()) else {
+            return;
+        };
+        let message = match reference.split_once(':') {
+            _ if motions.contains_key(reference) => return,
+            Some((group, name)) => match motions.get(group) {
+                Some(entries) if entries.iter().any(|m| m.name.as_deref() == Some(name)) => return,
+                Some(_) => format!(
+                    "Motion group \"{}\" has no motion named \"{}\"",
+                    group, name
+                ),
+                None => format!("Motion group \"{}\" does not exist", group),
+            },
+            None => format!("Motion group \"{}\" does not exist", reference),
+        };
+        self.push(Severity::Warning, "unknown-motion", pointer, message);
+    }
+
+    fn check_controller_refs(&mut self, model: &Model3, motions: &BTreeMap<String, Vec<Motion>>) {
+        let controllers = model.controllers.as_ref();
+
+        let param_hit = controllers
+            .and_then(|c| c.param_hit.as_ref())
+            .and_then(|c| c.items.as_ref())
+            .map(|items| ("/Controllers/ParamHit/Items", items));
+        let hit_params = model.hit_params.as_ref().map(|items| ("/HitParams", items));
+        for (prefix, items) in param_hit.into_iter().chain(hit_params) {
+            for (i, item) in items.iter().enumerate() {
+                for (key, reference) in [
+                    ("MaxMtn", &item.max_mtn),
+                    ("MinMtn", &item.min_mtn),
+                    ("BeginMtn", &item.begin_mtn),
+                    ("EndMtn", &item.end_mtn),
+                ] {
+                    self.check_motion_ref(
+                        motions,
+                        format!("{}/{}/{}", prefix, i, key),
+                        reference.as_deref(),
+                    );
+                }
+            }
+        }
+
+        let Some(controllers) = controllers else {
+            return;
+        };
+        for (i, item) in controllers
+            .key_trigger
+            .iter()
+            .flat_map(|c| c.items.iter().flatten())
+            .enumerate()
+        {
+            self.check_motion_ref(
+                motions,
+                format!("/Controllers/KeyTrigger/Items/{}/DownMtn", i),
+                item.down_mtn.as_deref(),
+            );
+        }
+        for (i, item) in controllers
+            .param_trigger
+            .iter()
+            .flat_map(|c| c.items.iter().flatten())
+            .enumerate()
+        {
+            for (j, threshold) in item.items.iter().flatten().enumerate() {
+                self.check_motion_ref(
+                    motions,
+                    format!("/Controllers/ParamTrigger/Items/{}/Items/{}/Motion", i, j),
+                    threshold.motion.as_deref(),
+                );
+            }
+        }
+    }
+
+    // -----------------------------------------------------------------------
+    // Cubism 2
+    // -----------------------------------------------------------------------
+
+    fn check_model2(&mut self, model: &Model2) {
+        match model.model.as_deref().filter(|m| !m.is_empty()) {
+            Some(moc) => {
+                self.check_file(Severity::Error, "/model".to_string(), moc);
+            }
+            None => self.push(
+                Severity::Error,
+                "missing-field",
+                "/model".to_string(),
+                "No moc file is referenced".to_string(),
+            ),
+        }
+
+        for (i, texture) in model.textures.iter().flatten().enumerate() {
+            let pointer = format!("/textures/{}", i);
+            if texture.is_empty() {
+                self.push(
+                    Severity::Error,
+                    "empty-texture",
+                    pointer,
+                    "Texture entry is empty".to_string(),
+                );
+            } else {
+                self.check_file(Severity::Error, pointer, texture);
+            }
+        }
+
+        self.check_optional_file(
+            Severity::Warning,
+            "/physics".to_string(),
+            model.physics.as_deref(),
+        );
+        self.check_optional_file(
+            Severity::Warning,
+            "/pose".to_string(),
+            model.pose.as_deref(),
+        );
+        for (i, exp) in model.expressions.iter().flatten().enumerate() {
+            self.check_optional_file(
+                Severity::Warning,
+                format!("/expressions/{}/file", i),
+                exp.file.as_deref(),
+            );
+        }
+
+        for (group, entries) in model.motions.iter().flatten() {
+            for (i, motion) in entries.iter().enumerate() {
+                let base = format!("/motions/{}/{}", escape(group), i);
+                self.check_optional_file(
+                    Severity::Warning,
+                    format!("{}/file", base),
+                    motion.file.as_deref(),
+                );
+                self.check_optional_file(
+                    Severity::Warning,
+                    format!("{}/sound", base),
+                    motion.sound.as_deref(),
+                );
+            }
+        }
+
+        self.check_duplicate_ids(
+            model
+                .hit_areas
+                .iter()
+                .flatten()
+                .enumerate()
+                .filter_map(|(i, a)| Some((format!("/hit_areas/{}/id", i), a.id.as_deref()?))),
+        );
+    }
+}
+
+/// Escape a key for use as a JSON pointer reference token.
+fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, data: &str) {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    fn find<'a>(report: &'a ValidationReport, pointer: &str) -> Option<&'a Diagnostic> {
+        report.diagnostics.iter().find(|d| d.pointer == pointer)
+    }
+
+    #[test]
+    fn test_malformed_fields_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write(dir, "model.moc3", "");
+        write(
+            dir,
+            "model.model3.json",
+            r#"{"Version":3,"FileReferences":{"Moc":"model.moc3","Textures":[null]},
+                "LipSync":1}"#,
+        );
+
+        let report = validate_model(&dir.join("model.model3.json")).unwrap();
+        assert_eq!(find(&report, "/LipSync").unwrap().code, "invalid-type");
+        let texture: Vec<_> = report
+            .diagnostics
+            .iter()
+            .filter(|d| d.pointer == "/FileReferences/Textures/0")
+            .map(|d| d.code)
+            .collect();
+        assert_eq!(texture, ["empty-texture"]);
+    }
+
+    #[test]
+    fn test_model3_diagnostics() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write(dir, "model.moc3", "");
+        write(dir, "tex.png", "");
+        write(
+            dir,
+            "idle.motion3.json",
+            r#"{"Meta":{"TotalPointCount":9,"TotalSegmentCount":1},
+                "Curves":[{"Segments":[0,0, 0,1,1]}]}"#,
+        );
+        write(
+            dir,
+            "model.model3.json",
+            r#"{"Version":3,"FileReferences":{
+                "Moc":"model.moc3",
+                "Textures":["tex.png","","gone.png"],
+                "Motions":{
+                    "Idle":[{"File":"idle.motion3.json","Name":"loop","NextMtn":"Idle:nope"}],
+                    "Tap/Body":[{"File":"tap.motion3.json","Sound":"tap.wav","NextMtn":"Idle:loop"}]
+                }},
+                "HitAreas":[{"Id":"Head","Motion":"Missing"},{"Id":"Head","Motion":"Tap/Body"}],
+                "Controllers":{"ParamHit":{"Items":[{"MaxMtn":"Idle","MinMtn":"Nope"}]}}}"#,
+        );
+
+        let report = validate_model(&dir.join("model.model3.json")).unwrap();
+        assert_eq!(report.version, CubismVersion::Cubism3);
+
+        let empty = find(&report, "/FileReferences/Textures/1").unwrap();
+        assert_eq!(
+            (empty.severity, empty.code),
+            (Severity::Error, "empty-texture")
+        );
+        assert_eq!(empty.fix.as_ref().unwrap().fixup, "model3_empty_textures");
+        assert_eq!(
+            find(&report, "/FileReferences/Textures/2").unwrap().code,
+            "missing-file"
+        );
+        assert!(find(&report, "/FileReferences/Textures/0").is_none());
+
+        let tap = "/FileReferences/Motions/Tap~1Body/0";
+        assert_eq!(
+            find(&report, &format!("{}/File", tap)).unwrap().code,
+            "missing-file"
+        );
+        assert!(find(&report, &format!("{}/Sound", tap)).is_some());
+        assert!(find(&report, &format!("{}/NextMtn", tap)).is_none());
+
+        let counts = find(&report, "/Meta/TotalPointCount").unwrap();
+        assert_eq!(counts.file, "idle.motion3.json");
+        assert_eq!(counts.fix.as_ref().unwrap().fixup, "motion3_counts");
+        assert!(find(&report, "/Meta/TotalSegmentCount").is_none());
+
+        for pointer in [
+            "/FileReferences/Motions/Idle/0/NextMtn",
+            "/HitAreas/0/Motion",
+            "/Controllers/ParamHit/Items/0/MinMtn",
+        ] {
+            assert_eq!(find(&report, pointer).unwrap().code, "unknown-motion");
+        }
+        assert!(find(&report, "/HitAreas/1/Motion").is_none());
+        assert!(find(&report, "/Controllers/ParamHit/Items/0/MaxMtn").is_none());
+        assert_eq!(
+            find(&report, "/HitAreas/1/Id").unwrap().code,
+            "duplicate-hit-area"
+        );
+        assert_eq!(
+            find(&report, "/Groups")
+                .unwrap()
+                .fix
+                .as_ref()
+                .unwrap()
+                .fixup,
+            "model3_groups"
+        );
+    }
+
+    #[test]
+    fn test_model2_diagnostics() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write(dir, "model.moc", "");
+        write(dir, "textures/texture_00.png", "");
+        write(dir, "motions/idle.mtn", "");
+        write(
+            dir,
+            "model.model.json",
+            r#"{"model":"model.moc","textures":["textures/texture_00.png"],
+                "physics":"physics.json",
+                "motions":{"idle":[{"file":"motions/idle.mtn"},{"file":"motions/gone.mtn"}]},
+                "hit_areas":[{"name":"head","id":"D_REF.HEAD"}]}"#,
+        );
+
+        let report = validate_model(&dir.join("model.model.json")).unwrap();
+        assert_eq!(report.version, CubismVersion::Cubism2);
+        let pointers: Vec<_> = report.diagnostics.iter().map(|d| &d.pointer).collect();
+        assert_eq!(pointers, ["/physics", "/motions/idle/1/file"]);
+        assert!(report
+            .diagnostics
+            .iter()
+            .all(|d| d.severity == Severity::Warning && d.file == "model.model.json"));
+    }
+
+    #[test]
+    fn test_model2_malformed_fields_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write(dir, "model.moc", "");
+        write(dir, "idle.mtn", "");
+        write(
+            dir,
+            "model.model.json",
+            r#"{"name":1,"model":"model.moc","textures":[null],
+                "motions":{"idle":[{"file":"idle.mtn","fade_in":"500"}]}}"#,
+        );
+
+        let report = validate_model(&dir.join("model.model.json")).unwrap();
+        assert_eq!(report.version, CubismVersion::Cubism2);
+        assert_eq!(find(&report, "/name").unwrap().code, "invalid-type");
+        assert_eq!(
+            find(&report, "/motions/idle/0/fade_in").unwrap().code,
+            "invalid-type"
+        );
+        let texture: Vec<_> = report
+            .diagnostics
+            .iter()
+            .filter(|d| d.pointer == "/textures/0")
+            .map(|d| d.code)
+            .collect();
+        assert_eq!(texture, ["empty-texture"]);
+    }
+
+    #[test]
+    fn test_missing_moc_is_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(
+            tmp.path(),
+            "a.model3.json",
+            r#"{"FileReferences":{},"Groups":[]}"#,
+        );
+        let report = validate_model(&tmp.path().join("a.model3.json")).unwrap();
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(report.diagnostics[0].pointer, "/FileReferences/Moc");
+        assert_eq!(report.diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_unparseable_descriptor_is_err() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "a.model3.json", "{not json");
+        assert!(validate_model(&tmp.path().join("a.model3.json")).is_err());
+    }
+}
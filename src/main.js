@@ -534,11 +534,18 @@ function createSeparator() {
   return el;
 }
 
+// Position, scale and behaviour toggles are remembered per model; without
+// one loaded they go to the global defaults
+function saveModelSetting(key, value) {
+  if (!currentModelPath) return invoke('set_setting', { key, value });
+  return invoke('set_model_setting', { path: currentModelPath, key, value });
+}
+
 function showContextMenu(x, y) {
   ctxMenu.innerHTML = '';
   ctxMenu.appendChild(createMenuItem('Tap Motions', {
     toggle: tapMotion,
-    action: () => invoke('set_setting', { key: 'tap_motion', value: String(!tapMotion) }),
+    action: () => saveModelSetting('tap_motion', String(!tapMotion)),
   }));
   ctxMenu.appendChild(createMenuItem('Show Hit Areas', {
     toggle: showHitAreas,
@@ -546,11 +553,11 @@ function showContextMenu(x, y) {
   }));
   ctxMenu.appendChild(createMenuItem('Mouse Tracking', {
     toggle: mouseTracking,
-    action: () => invoke('set_setting', { key: 'mouse_tracking', value: String(!mouseTracking) }),
+    action: () => saveModelSetting('mouse_tracking', String(!mouseTracking)),
   }));
   ctxMenu.appendChild(createMenuItem('Lock Model', {
     toggle: lockModel,
-    action: () => invoke('set_setting', { key: 'lock_model', value: String(!lockModel) }),
+    action: () => saveModelSetting('lock_model', String(!lockModel)),
   }));
   ctxMenu.appendChild(createMenuItem('Debug Border', {
     toggle: showBorder,
@@ -603,14 +610,14 @@ let scaleSaveTimeout = null;
 function debouncedSaveScale(scale) {
   clearTimeout(scaleSaveTimeout);
   scaleSaveTimeout = setTimeout(() => {
-    invoke('set_setting', { key: 'model_scale', value: String(scale) });
+    saveModelSetting('model_scale', String(scale));
   }, 300);
 }
 
 function savePosition() {
   if (!currentModel) return;
-  invoke('set_setting', { key: 'model_x', value: String(currentModel.x) });
-  invoke('set_setting', { key: 'model_y', value: String(currentModel.y) });
+  saveModelSetting('model_x', String(currentModel.x));
+  saveModelSetting('model_y', String(currentModel.y));
 }
 
 // --- Motion map helpers ---
@@ -895,7 +902,7 @@ function applyIntimacyBonus(entry) {
   const bonus = intim?.Bonus ?? 0;
   if (bonus === 0) return;
   intimacyValue = Math.max(intimacyConfig.minValue, Math.min(intimacyConfig.maxValue, intimacyValue + bonus));
-  invoke('set_setting', { key: `intimacy:${currentModelPath}`, value: String(intimacyValue) }).catch(() => {});
+  invoke('set_model_intimacy', { path: currentModelPath, value: intimacyValue }).catch(() => {});
   console.log(`[intimacy] ${bonus > 0 ? '+' : ''}${bonus} → ${intimacyValue}`);
 }
 
@@ -1042,6 +1049,12 @@ function executeOneCommand(cmd) {
       console.log(`[cmd] replace_tex deferred: ${parts.slice(1).join(' ')}`);
       break;
     }
+    case 'change_cos': {
+      // Backend resolves the path and emits load-model, keeping position/scale
+      const target = parts.slice(1).join(' ');
+      invoke('change_cos', { target }).catch((e) => console.warn(`[cmd] change_cos failed: ${e}`));
+      break;
+    }
     default:
       console.log(`[cmd] unknown command: ${cmd}`);
   }
@@ -1311,7 +1324,7 @@ async function loadModel(modelPath) {
       origRender(renderer);
     };
 
-    // Load saved position/scale from config
+    // Load this model's saved position/scale and toggles
     const config = await invoke('get_config');
 
     if (config.model_scale != null) {
@@ -1422,7 +1435,10 @@ async function loadModel(modelPath) {
     modelMotions = rawJson.FileReferences?.Motions || rawJson.motions || {};
 
     // Load custom motion overrides from settings
-    currentModelPath = modelPath.replace('model://localhost/', '');
+    // Per-model settings are keyed by the library entry, which may have
+    // loaded one of its costume variants
+    const loadedPath = modelPath.replace('model://localhost/', '');
+    currentModelPath = (await invoke('get_config').catch(() => null))?.current_model || loadedPath;
     let customJsonStr = null;
     try {
       customJsonStr = await invoke('get_custom_motions', { path: currentModelPath });
@@ -1597,8 +1613,8 @@ async function loadModel(modelPath) {
       };
       // Try to load persisted intimacy value
       try {
-        const saved = await invoke('get_setting', { key: `intimacy:${currentModelPath}` });
-        if (saved !== null && saved !== undefined) intimacyValue = parseFloat(saved);
+        const saved = await invoke('get_model_intimacy', { path: currentModelPath });
+        if (saved !== null && saved !== undefined) intimacyValue = saved;
         else intimacyValue = intimacyConfig.initValue;
       } catch {
         intimacyValue = intimacyConfig.initValue;
@@ -1828,6 +1844,7 @@ async function loadModel(modelPath) {
 
     currentModel = model;
     showBorder = config.show_border;
+    tapMotion = config.tap_motion;
     showHitAreas = config.show_hit_areas;
     lockModel = config.lock_model;
     mouseTracking = config.mouse_tracking;